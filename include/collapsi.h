@@ -0,0 +1,146 @@
+#ifndef COLLAPSI_H
+#define COLLAPSI_H
+
+/* Generated by cbindgen from src/ffi.rs: do not edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/*
+ * A position, with its history since it was created
+ */
+typedef struct CollapsiBoard CollapsiBoard;
+
+/*
+ * A square of the grid
+ */
+typedef struct CollapsiSquare {
+  uint8_t row;
+  uint8_t col;
+} CollapsiSquare;
+
+#ifdef __cplusplus
+extern "C" {
+#endif  // __cplusplus
+
+/*
+ * Read a position from its NUL-terminated notation, for example
+ * "1223/4121r/3123/1b314/0", returning null if it is invalid
+ *
+ * # Safety
+ *
+ * `notation` must be null or point to a NUL-terminated string.
+ */
+struct CollapsiBoard *collapsi_board_new(const char *notation);
+
+/*
+ * The starting deal with the given ID, under the old rules (v1.1) if `old`
+ * is true, returning null if there is no such deal
+ */
+struct CollapsiBoard *collapsi_board_from_deal_id(uint64_t id, bool old);
+
+/*
+ * Release a board
+ *
+ * # Safety
+ *
+ * `board` must be null or a handle from this API that has not been freed.
+ */
+void collapsi_board_free(struct CollapsiBoard *board);
+
+/*
+ * Write the notation of a position into `buffer`, NUL-terminated and
+ * truncated to fit `size` bytes
+ *
+ * Returns the length of the full notation, not counting the NUL, like
+ * `snprintf`, so a return value of `size` or more means it was truncated.
+ *
+ * # Safety
+ *
+ * `board` must be null or a live handle, and `buffer` must be null or have
+ * room for `size` bytes.
+ */
+size_t collapsi_board_notation(const struct CollapsiBoard *board, char *buffer, size_t size);
+
+/*
+ * The player to move: 0 for red, 1 for blue, or -1 for a null handle
+ *
+ * # Safety
+ *
+ * `board` must be null or a live handle.
+ */
+int32_t collapsi_board_turn(const struct CollapsiBoard *board);
+
+/*
+ * Write the squares the player to move can move to into `moves`, at most
+ * `capacity` of them
+ *
+ * Returns the number of legal moves, which may be more than `capacity`. No
+ * position has more than 16, and 0 means the game is over.
+ *
+ * # Safety
+ *
+ * `board` must be null or a live handle, and `moves` must be null or have
+ * room for `capacity` squares.
+ */
+size_t collapsi_legal_moves(const struct CollapsiBoard *board, struct CollapsiSquare *moves, size_t capacity);
+
+/*
+ * Check a path reported for the player to move, for example by a physical
+ * board: `length` squares from the pawn's square to its destination, each a
+ * neighbour of the last (possibly around an edge), covering exactly the
+ * distance on the pawn's card
+ *
+ * Returns whether it is a legal path; if so the move can be made with
+ * `collapsi_make_move` to its last square.
+ *
+ * # Safety
+ *
+ * `board` must be null or a live handle, and `squares` must be null or
+ * point to `length` squares.
+ */
+bool collapsi_check_path(const struct CollapsiBoard *board, const struct CollapsiSquare *squares, size_t length);
+
+/*
+ * Move the player to move to the given square, returning false (and leaving
+ * the board unchanged) if that is not a legal move
+ *
+ * # Safety
+ *
+ * `board` must be null or a live handle.
+ */
+bool collapsi_make_move(struct CollapsiBoard *board, uint8_t row, uint8_t col);
+
+/*
+ * Take back the last move made since the board was created, returning false
+ * if there is none
+ *
+ * # Safety
+ *
+ * `board` must be null or a live handle.
+ */
+bool collapsi_undo_move(struct CollapsiBoard *board);
+
+/*
+ * Solve a position with perfect play, writing its score to `score` and a
+ * best move to `best_move`
+ *
+ * Returns false if there is no move to make, because the game is over (the
+ * score is still written) or the handle is null. Either output may be null
+ * if it is not wanted.
+ *
+ * # Safety
+ *
+ * `board` must be null or a live handle, and `best_move` and `score` must
+ * each be null or valid to write to.
+ */
+bool collapsi_solve(struct CollapsiBoard *board, struct CollapsiSquare *best_move, int8_t *score);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* COLLAPSI_H */
@@ -0,0 +1,406 @@
+//! Lazy enumeration of all starting deals up to symmetry, with random access
+//! by index
+
+use crate::{Board, CollapsiVersion, Point, SIZE};
+use rand::Rng;
+use rand::seq::SliceRandom;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Positions of the blue pawn's jack (counting along rows from the top-left),
+/// with the number of deals each position represents
+///
+/// The red pawn's jack is always at the top-left. See `Board::all_boards`.
+pub(crate) const BLUE_JACKS: [(usize, u64); 5] = [(1, 4), (2, 2), (5, 4), (6, 4), (10, 1)];
+
+/// Number of cards of each value other than the jacks, indexed by value
+const CARD_COUNTS: [usize; 5] = [0, 4, 4, 4, 2];
+
+/// Number of ways to lay out the cards other than the jacks
+pub(crate) const LAYOUTS: u64 = multinomial(&CARD_COUNTS);
+
+/// All starting deals up to symmetry, in the same order as
+/// `Board::all_boards`, generated on demand
+///
+/// Deal `i` has its blue jack at `BLUE_JACKS[i / LAYOUTS]`, and the other
+/// cards in the `(i % LAYOUTS)`th arrangement in lexicographic order.
+#[derive(Clone, Copy, Debug)]
+pub struct Deals {
+    /// The card value of the jacks the pawns start on
+    start_value: u8,
+}
+
+impl Deals {
+    pub fn new(version: CollapsiVersion) -> Deals {
+        let start_value = match version {
+            CollapsiVersion::V1_1 => 0,
+            CollapsiVersion::V1_3 => 1,
+        };
+        Deals { start_value }
+    }
+
+    /// Number of deals
+    pub fn len(&self) -> u64 {
+        BLUE_JACKS.len() as u64 * LAYOUTS
+    }
+
+    /// Whether there are no deals (never true)
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Total number of real deals represented, taking weights into account
+    pub fn total_weight(&self) -> u64 {
+        BLUE_JACKS.iter().map(|(_, weight)| weight).sum::<u64>() * LAYOUTS
+    }
+
+    /// The deal at a given index, with its weight
+    pub fn get(&self, index: u64) -> Option<(Board, u64)> {
+        if index >= self.len() {
+            return None;
+        }
+        let (pawn2, weight) = BLUE_JACKS[(index / LAYOUTS) as usize];
+        Some((self.board(pawn2, index % LAYOUTS), weight))
+    }
+
+    /// Number of deals with the red jack at the top-left, without reducing by
+    /// rotations and reflections
+    pub fn unreduced_len(&self) -> u64 {
+        (SIZE * SIZE - 1) as u64 * LAYOUTS
+    }
+
+    /// The deal at a given index in the list of all deals with the red jack at
+    /// the top-left, without reducing by rotations and reflections
+    ///
+    /// Every possible deal is a toroidal shift of exactly one of these.
+    pub fn get_unreduced(&self, index: u64) -> Option<Board> {
+        if index >= self.unreduced_len() {
+            return None;
+        }
+        let pawn2 = (index / LAYOUTS) as usize + 1;
+        Some(self.board(pawn2, index % LAYOUTS))
+    }
+
+    /// The deal with the red jack at the top-left, the blue jack at `pawn2`
+    /// (counting along rows), and the other cards in the `layout`th
+    /// arrangement, whether or not it is one of the representatives
+    pub(crate) fn board(&self, pawn2: usize, layout: u64) -> Board {
+        let mut cards = [[0; SIZE]; SIZE];
+        let mut others = unrank(layout, CARD_COUNTS).into_iter();
+        for (i, card) in cards.iter_mut().flatten().enumerate() {
+            *card = if i == 0 || i == pawn2 {
+                self.start_value
+            } else {
+                others.next().expect("Exactly enough cards")
+            };
+        }
+        Board {
+            cards,
+            pawns: [Point(0, 0), Point(pawn2 / SIZE, pawn2 % SIZE)],
+            turn: 0,
+            start_ply: 0,
+            moves: vec![],
+        }
+    }
+
+    /// The index of a board in this list of deals, or None if it is not one
+    /// of them
+    ///
+    /// This is the inverse of `get`.
+    pub fn index_of(&self, board: &Board) -> Option<u64> {
+        if board.ply() != 0 || board.pawns[0] != Point(0, 0) {
+            return None;
+        }
+        let Point(x, y) = board.pawns[1];
+        let pawn2 = x * SIZE + y;
+        let jack = BLUE_JACKS.iter().position(|(p, _)| *p == pawn2)?;
+        let mut layout = vec![];
+        for (i, card) in board.cards.iter().flatten().enumerate() {
+            if i == 0 || i == pawn2 {
+                if *card != self.start_value {
+                    return None;
+                }
+            } else {
+                layout.push(*card);
+            }
+        }
+        let rank = rank(&layout, CARD_COUNTS)?;
+        Some(jack as u64 * LAYOUTS + rank)
+    }
+
+    /// A random deal, chosen with probability proportional to its weight
+    ///
+    /// Each deal is then as likely as the real deals it represents, so a
+    /// sample of these can be used to estimate statistics over all real deals
+    /// without weighting them again.
+    pub fn sample(&self, rng: &mut impl Rng) -> Board {
+        let mut target = rng.random_range(0..self.total_weight() / LAYOUTS);
+        let (pawn2, _) = BLUE_JACKS
+            .iter()
+            .find(|(_, weight)| {
+                let found = target < *weight;
+                target = target.saturating_sub(*weight);
+                found
+            })
+            .expect("Target is less than the total weight");
+        self.board(*pawn2, rng.random_range(0..LAYOUTS))
+    }
+
+    /// Iterate through all deals in order
+    pub fn iter(&self) -> impl Iterator<Item = (Board, u64)> + '_ {
+        (0..self.len()).map(|i| self.get(i).expect("In range"))
+    }
+
+    /// Iterate through all deals in parallel
+    ///
+    /// Deals are generated as they are needed, so this uses constant memory.
+    /// Since the iterator is indexed, it can be split, stepped through or
+    /// zipped with other indexed iterators.
+    #[cfg(feature = "parallel")]
+    pub fn par_iter(&self) -> impl IndexedParallelIterator<Item = (Board, u64)> + '_ {
+        (0..self.len() as usize)
+            .into_par_iter()
+            .map(|i| self.get(i as u64).expect("In range"))
+    }
+}
+
+/// Number of distinct arrangements of a multiset with `counts[v]` copies of
+/// each value `v`
+const fn multinomial(counts: &[usize; 5]) -> u64 {
+    let mut total = 0;
+    let mut result: u64 = 1;
+    let mut v = 0;
+    while v < counts.len() {
+        // Multiply by (total + counts[v]) choose counts[v], one factor at a time
+        let mut k = 1;
+        while k <= counts[v] {
+            total += 1;
+            result = result * total as u64 / k as u64;
+            k += 1;
+        }
+        v += 1;
+    }
+    result
+}
+
+/// The `rank`th arrangement (in lexicographic order) of a multiset with
+/// `counts[v]` copies of each value `v`
+fn unrank(mut rank: u64, mut counts: [usize; 5]) -> Vec<u8> {
+    let length = counts.iter().sum();
+    let mut out = Vec::with_capacity(length);
+    while out.len() < length {
+        for value in 0..counts.len() {
+            if counts[value] == 0 {
+                continue;
+            }
+            counts[value] -= 1;
+            let with_value = multinomial(&counts);
+            if rank < with_value {
+                out.push(value as u8);
+                break;
+            }
+            rank -= with_value;
+            counts[value] += 1;
+        }
+    }
+    out
+}
+
+/// The position of `values` in the lexicographic order of arrangements of a
+/// multiset with `counts[v]` copies of each value `v`, or None if it is not
+/// such an arrangement
+///
+/// This is the inverse of `unrank`.
+fn rank(values: &[u8], mut counts: [usize; 5]) -> Option<u64> {
+    if values.len() != counts.iter().sum::<usize>() {
+        return None;
+    }
+    let mut rank = 0;
+    for value in values {
+        let value = *value as usize;
+        if counts.get(value).is_none_or(|count| *count == 0) {
+            return None;
+        }
+        for smaller in 0..value {
+            if counts[smaller] > 0 {
+                counts[smaller] -= 1;
+                rank += multinomial(&counts);
+                counts[smaller] += 1;
+            }
+        }
+        counts[value] -= 1;
+    }
+    Some(rank)
+}
+
+impl Board {
+    /// A random starting deal, as dealt from a shuffled deck
+    ///
+    /// The deck has four aces, four 2s, four 3s, two 4s and two jacks. The red
+    /// pawn goes on the first jack in reading order and the blue pawn on the
+    /// second.
+    pub fn random_deal(version: CollapsiVersion, rng: &mut impl Rng) -> Board {
+        let jack = Deals::new(version).start_value;
+        let mut deck: Vec<Option<u8>> = CARD_COUNTS
+            .iter()
+            .enumerate()
+            .flat_map(|(value, count)| std::iter::repeat_n(Some(value as u8), *count))
+            .chain([None, None])
+            .collect();
+        deck.shuffle(rng);
+
+        let mut cards = [[0; SIZE]; SIZE];
+        let mut jacks = vec![];
+        for (i, card) in deck.into_iter().enumerate() {
+            let point = Point(i / SIZE, i % SIZE);
+            cards[point.0][point.1] = card.unwrap_or_else(|| {
+                jacks.push(point);
+                jack
+            });
+        }
+        Board {
+            cards,
+            pawns: [jacks[0], jacks[1]],
+            turn: 0,
+            start_ply: 0,
+            moves: vec![],
+        }
+    }
+
+    /// The starting deal with the given ID, which is its index in `Deals`
+    pub fn from_deal_id(version: CollapsiVersion, id: u64) -> Result<Board, &'static str> {
+        match Deals::new(version).get(id) {
+            Some((board, _)) => Ok(board),
+            None => Err("Deal IDs must be less than 15765750"),
+        }
+    }
+
+    /// The ID of this starting deal, if it is one of the representatives in
+    /// `Deals`
+    ///
+    /// Use `canonical_deal` first to find the ID of any other deal.
+    pub fn deal_id(&self) -> Option<u64> {
+        Deals::new(self.deal_version()?).index_of(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn factorial(n: u64) -> u64 {
+        (1..=n).product()
+    }
+
+    #[test]
+    fn multinomial_counts_arrangements() {
+        assert_eq!(multinomial(&[0, 0, 0, 0, 0]), 1);
+        assert_eq!(multinomial(&[0, 2, 1, 1, 0]), 12);
+        assert_eq!(
+            LAYOUTS,
+            factorial(14) / (factorial(4) * factorial(4) * factorial(4) * factorial(2))
+        );
+        let deals = Deals::new(CollapsiVersion::V1_3);
+        assert_eq!(deals.len(), BLUE_JACKS.len() as u64 * LAYOUTS);
+        assert_eq!(deals.total_weight(), deals.unreduced_len());
+    }
+
+    #[test]
+    fn arrangements_are_ranked_in_order() {
+        let counts = [1, 2, 1, 0, 1];
+        let arrangements: Vec<Vec<u8>> = (0..multinomial(&counts))
+            .map(|rank| unrank(rank, counts))
+            .collect();
+        let expected: Vec<Vec<u8>> = [0, 1, 1, 2, 4]
+            .into_iter()
+            .permutations(5)
+            .sorted()
+            .dedup()
+            .collect();
+        assert_eq!(arrangements, expected);
+        for (i, arrangement) in arrangements.iter().enumerate() {
+            assert_eq!(rank(arrangement, counts), Some(i as u64));
+        }
+        assert_eq!(rank(&[0, 1, 1, 2], counts), None);
+        assert_eq!(rank(&[0, 1, 1, 1, 4], counts), None);
+    }
+
+    #[test]
+    fn index_of_inverts_get() {
+        for version in [CollapsiVersion::V1_1, CollapsiVersion::V1_3] {
+            let deals = Deals::new(version);
+            let indices = (0..deals.len()).step_by(7919).chain(
+                (0..BLUE_JACKS.len() as u64).flat_map(|j| [j * LAYOUTS, (j + 1) * LAYOUTS - 1]),
+            );
+            for i in indices {
+                let (board, _) = deals.get(i).unwrap();
+                assert_eq!(deals.index_of(&board), Some(i));
+                assert_eq!(board.deal_id(), Some(i));
+            }
+            assert!(deals.get(deals.len()).is_none());
+        }
+    }
+
+    #[test]
+    fn index_of_rejects_other_boards() {
+        let deals = Deals::new(CollapsiVersion::V1_3);
+        let (mut board, _) = deals.get(12345).unwrap();
+        assert_eq!(Deals::new(CollapsiVersion::V1_1).index_of(&board), None);
+        let m = *board.legal_moves().first().unwrap();
+        board.make_move(m);
+        assert_eq!(deals.index_of(&board), None);
+        assert_eq!(deals.index_of(&Board::example()), None);
+    }
+
+    #[test]
+    fn random_deals_are_legal_and_seeded() {
+        for version in [CollapsiVersion::V1_1, CollapsiVersion::V1_3] {
+            let jack = Deals::new(version).start_value;
+            let mut rng = StdRng::seed_from_u64(7);
+            let deals: Vec<Board> = (0..20)
+                .map(|_| Board::random_deal(version, &mut rng))
+                .collect();
+            let mut again = StdRng::seed_from_u64(7);
+            for board in &deals {
+                assert_eq!(
+                    board.notation(),
+                    Board::random_deal(version, &mut again).notation()
+                );
+                assert_eq!(board.deal_version(), Some(version));
+                assert!(board.pawns.iter().all(|pawn| board.card(*pawn) == jack));
+                assert!(board.pawns[0] < board.pawns[1]);
+            }
+            assert!(
+                deals
+                    .iter()
+                    .any(|board| board.notation() != deals[0].notation())
+            );
+        }
+    }
+
+    #[test]
+    fn samples_are_in_proportion_to_weights() {
+        let deals = Deals::new(CollapsiVersion::V1_3);
+        let mut rng = StdRng::seed_from_u64(1);
+        let samples = 15_000;
+        let mut counts = [0u64; BLUE_JACKS.len()];
+        for _ in 0..samples {
+            let board = deals.sample(&mut rng);
+            let index = deals.index_of(&board).expect("Samples are representatives");
+            counts[(index / LAYOUTS) as usize] += 1;
+        }
+        let total: u64 = BLUE_JACKS.iter().map(|(_, weight)| weight).sum();
+        for (count, (_, weight)) in counts.iter().zip(BLUE_JACKS) {
+            let expected = (samples * weight / total) as f64;
+            // Within four standard deviations, roughly
+            assert!(
+                (*count as f64 - expected).abs() < 4.0 * expected.sqrt(),
+                "{:?}",
+                counts
+            );
+        }
+    }
+}
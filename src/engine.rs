@@ -0,0 +1,407 @@
+//! A line-based protocol for driving the engine from other programs, modelled
+//! on the Universal Chess Interface (UCI)
+//!
+//! Commands, one per line:
+//!
+//! - `uci`: identify the engine and list its options, ending with `uciok`
+//! - `isready`: reply `readyok`
+//! - `setoption name Rules value v1.1` (or `v1.3`, the default): the rules
+//!   used by `position deal`
+//! - `setoption name Hash value <MB>`: memory for the transposition table used
+//!   to order moves (0 to search in the natural order)
+//! - `ucinewgame`: stop any search and go back to the example position;
+//!   nothing else carries over between searches, since each has its own
+//!   transposition table
+//! - `position <notation> [moves <move> ...]` or
+//!   `position deal <id> [moves <move> ...]`: stop any search and set up a
+//!   position, from board notation or a deal ID, then make some moves
+//! - `go [depth <plies>] [nodes <n>] [movetime <ms>] [infinite]`: stop any
+//!   search and start a new one in the background
+//! - `stop`: end the search early, still reporting its best move
+//! - `d`: show the current position
+//! - `quit`
+//!
+//! A search first looks ahead with the heuristic evaluation at increasing
+//! depths, then (unless `depth` was given) solves the position exactly. The
+//! `nodes` and `movetime` limits and `stop` apply to both stages. It reports
+//! progress as lines like
+//! `info depth 3 score cp 14 nodes 85 time 2 pv 10`, with the score from the
+//! point of view of the player to move: `cp` for a heuristic score, or
+//! `cards` for a proven result (the cards remaining at the end of the game,
+//! negative if the player to move loses). It ends with `bestmove <move>`, or
+//! `bestmove none` if the game is over.
+//!
+//! Moves are written as the row then column of the destination, for example
+//! `10` for row 1, column 0.
+
+use crate::heuristic::{self, Weights};
+use crate::search::{MoveOrdering, SearchOptions};
+use crate::{Board, CollapsiVersion, Instant, Point, SIZE};
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Deepest heuristic search before solving exactly, if no depth is given
+const HEURISTIC_DEPTH: usize = 4;
+
+/// Approximate memory used by each transposition table entry, in bytes
+const TABLE_ENTRY_SIZE: usize = 32;
+
+/// The limits given to `go`
+#[derive(Default)]
+struct Limits {
+    depth: Option<usize>,
+    nodes: Option<u64>,
+    movetime: Option<Duration>,
+}
+
+/// The engine's state between commands
+pub struct Engine<W> {
+    board: Board,
+    version: CollapsiVersion,
+
+    /// Size of the transposition table, in megabytes
+    hash: usize,
+
+    output: Arc<Mutex<W>>,
+    stop: Arc<AtomicBool>,
+    search: Option<JoinHandle<()>>,
+}
+
+impl<W: Write + Send + 'static> Engine<W> {
+    /// An engine writing its replies to `output`, starting from the example
+    /// position
+    pub fn new(output: W) -> Engine<W> {
+        Engine {
+            board: Board::example(),
+            version: CollapsiVersion::V1_3,
+            hash: 16,
+            output: Arc::new(Mutex::new(output)),
+            stop: Arc::new(AtomicBool::new(false)),
+            search: None,
+        }
+    }
+
+    /// Follow commands from `input` until `quit` or the end of the input,
+    /// then wait for any search to finish
+    pub fn run(&mut self, input: impl BufRead) {
+        for line in input.lines() {
+            let Ok(line) = line else { break };
+            if !self.command(&line) {
+                return;
+            }
+        }
+        self.wait();
+    }
+
+    /// Follow one command, returning false if it was `quit`
+    pub fn command(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => (),
+            ["uci"] => {
+                send(&self.output, "id name Collapsi solver");
+                send(
+                    &self.output,
+                    "option name Rules type combo default v1.3 var v1.1 var v1.3",
+                );
+                send(
+                    &self.output,
+                    "option name Hash type spin default 16 min 0 max 4096",
+                );
+                send(&self.output, "uciok");
+            }
+            ["isready"] => send(&self.output, "readyok"),
+            ["setoption", rest @ ..] => self.set_option(rest),
+            ["ucinewgame"] => {
+                self.stop.store(true, Ordering::Relaxed);
+                self.wait();
+                self.board = Board::example();
+            }
+            ["position", rest @ ..] => {
+                self.stop.store(true, Ordering::Relaxed);
+                self.wait();
+                match self.position(rest) {
+                    Ok(board) => self.board = board,
+                    Err(message) => send(&self.output, &format!("info string {}", message)),
+                }
+            }
+            ["go", rest @ ..] => match parse_limits(rest) {
+                Ok(limits) => self.go(limits),
+                Err(message) => send(&self.output, &format!("info string {}", message)),
+            },
+            ["stop"] => {
+                self.stop.store(true, Ordering::Relaxed);
+                self.wait();
+            }
+            ["d"] => send(
+                &self.output,
+                &format!("{}\n{}", self.board, self.board.notation()),
+            ),
+            ["quit"] => {
+                self.stop.store(true, Ordering::Relaxed);
+                self.wait();
+                return false;
+            }
+            _ => send(
+                &self.output,
+                &format!("info string Unknown command: {}", line),
+            ),
+        }
+        true
+    }
+
+    fn set_option(&mut self, words: &[&str]) {
+        match words {
+            ["name", "Rules", "value", "v1.1"] => self.version = CollapsiVersion::V1_1,
+            ["name", "Rules", "value", "v1.3"] => self.version = CollapsiVersion::V1_3,
+            ["name", "Hash", "value", size] if size.parse::<usize>().is_ok() => {
+                self.hash = size.parse().expect("Just checked");
+            }
+            _ => send(
+                &self.output,
+                &format!("info string Unknown option: {}", words.join(" ")),
+            ),
+        }
+    }
+
+    /// The position described by the arguments of `position`
+    fn position(&self, words: &[&str]) -> Result<Board, String> {
+        let (mut board, moves) = match words {
+            ["deal", id, rest @ ..] => {
+                let id = id.parse().map_err(|_| "Deal IDs must be numbers")?;
+                (Board::from_deal_id(self.version, id)?, rest)
+            }
+            [notation, rest @ ..] => (
+                Board::new(notation).map_err(|message| format!("Invalid board: {}", message))?,
+                rest,
+            ),
+            [] => return Err("position needs a board".to_string()),
+        };
+        let moves = match moves {
+            [] => &[][..],
+            ["moves", moves @ ..] => moves,
+            _ => return Err("Expected moves after the board".to_string()),
+        };
+        for m in moves {
+            let point = parse_move(m).ok_or(format!("Invalid move: {}", m))?;
+            if !board.legal_moves().contains(&point) {
+                return Err(format!("Illegal move: {}", m));
+            }
+            board.make_move(point);
+        }
+        Ok(board)
+    }
+
+    /// Start searching the current position in the background
+    fn go(&mut self, limits: Limits) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.wait();
+        self.stop.store(false, Ordering::Relaxed);
+        let options = SearchOptions {
+            max_nodes: limits.nodes,
+            deadline: limits.movetime.map(|time| Instant::now() + time),
+            stop: Some(Arc::clone(&self.stop)),
+            ordering: if self.hash > 0 {
+                MoveOrdering::TranspositionTable
+            } else {
+                MoveOrdering::Natural
+            },
+            table_size: Some(self.hash * 1024 * 1024 / TABLE_ENTRY_SIZE),
+            ..Default::default()
+        };
+        let board = self.board.clone();
+        let output = Arc::clone(&self.output);
+        self.search = Some(thread::spawn(move || {
+            search(board, limits.depth, options, &output);
+        }));
+    }
+
+    /// Wait for the search in progress, if any, to finish
+    fn wait(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.join().expect("The search does not panic");
+        }
+    }
+}
+
+/// Search a position, reporting progress and the best move to `output`
+fn search<W: Write>(
+    mut board: Board,
+    depth: Option<usize>,
+    options: SearchOptions,
+    output: &Mutex<W>,
+) {
+    let started = Instant::now();
+    let elapsed = || started.elapsed().as_millis();
+    let stopped = || {
+        options
+            .stop
+            .as_ref()
+            .is_some_and(|stop| stop.load(Ordering::Relaxed))
+            || options
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    };
+    // Scores are from red's point of view, but reported for the player to move
+    let sign = if board.turn == 0 { 1 } else { -1 };
+    let weights = Weights::default();
+
+    // The node limit covers all the searches together
+    let mut nodes = 0;
+
+    // Any legal move will do if the search is stopped before finding one
+    let mut best = board.legal_moves().first().copied();
+    for d in 1..=depth.unwrap_or(HEURISTIC_DEPTH) {
+        if best.is_none() || stopped() {
+            break;
+        }
+        let result = board.best_move_heuristic_limited(d, &weights, remaining(&options, nodes));
+        nodes += result.nodes;
+        if !result.completed {
+            break;
+        }
+        let (Some(m), score) = result.value else {
+            break;
+        };
+        best = Some(m);
+        let score = match heuristic::proven_score(score) {
+            Some(cards) => format!("cards {}", sign * cards as i32),
+            None => format!("cp {}", sign * score),
+        };
+        send(
+            output,
+            &format!(
+                "info depth {} score {} nodes {} time {} pv {}",
+                d,
+                score,
+                nodes,
+                elapsed(),
+                format_move(m)
+            ),
+        );
+    }
+
+    if depth.is_none() && best.is_some() && !stopped() {
+        let result = board.best_move_by_cards_remaining_limited(remaining(&options, nodes));
+        nodes += result.nodes;
+        if result.completed {
+            let (m, score) = result.value;
+            best = m;
+            let pv = m.map_or(vec![], |m| {
+                principal_variation(&mut board, m, &options, &mut nodes)
+            });
+            send(
+                output,
+                &format!(
+                    "info depth {} score cards {} nodes {} time {} pv {}",
+                    pv.len(),
+                    sign * score as i32,
+                    nodes,
+                    elapsed(),
+                    pv.iter()
+                        .map(|m| format_move(*m))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                ),
+            );
+        } else {
+            send(
+                output,
+                &format!(
+                    "info nodes {} time {} string exact search stopped",
+                    nodes,
+                    elapsed()
+                ),
+            );
+        }
+    }
+
+    match best {
+        Some(m) => send(output, &format!("bestmove {}", format_move(m))),
+        None => send(output, "bestmove none"),
+    }
+}
+
+/// The limits for the next search, given the nodes already searched
+fn remaining(options: &SearchOptions, nodes: u64) -> SearchOptions {
+    SearchOptions {
+        max_nodes: options.max_nodes.map(|max| max.saturating_sub(nodes)),
+        ..options.clone()
+    }
+}
+
+/// The moves of perfect play from this position to the end of the game,
+/// starting with `first`, the best move already found, or as many as could be
+/// found within the limits, adding the nodes searched to `nodes`
+fn principal_variation(
+    board: &mut Board,
+    first: Point,
+    options: &SearchOptions,
+    nodes: &mut u64,
+) -> Vec<Point> {
+    let mut pv = vec![first];
+    board.make_move(first);
+    while options.max_nodes.is_none_or(|max| *nodes < max) {
+        let result = board.best_move_by_cards_remaining_limited(remaining(options, *nodes));
+        *nodes += result.nodes;
+        match result.value.0 {
+            Some(m) if result.completed => {
+                pv.push(m);
+                board.make_move(m);
+            }
+            _ => break,
+        }
+    }
+    for _ in &pv {
+        board.undo_move();
+    }
+    pv
+}
+
+/// Read the arguments of `go`
+fn parse_limits(words: &[&str]) -> Result<Limits, String> {
+    let mut limits = Limits::default();
+    let mut words = words.iter();
+    while let Some(word) = words.next() {
+        let mut value = || {
+            words
+                .next()
+                .and_then(|value| value.parse::<u64>().ok())
+                .ok_or(format!("{} requires a number", word))
+        };
+        match *word {
+            "depth" => limits.depth = Some(value()? as usize),
+            "nodes" => limits.nodes = Some(value()?),
+            "movetime" => limits.movetime = Some(Duration::from_millis(value()?)),
+            "infinite" => (),
+            _ => return Err(format!("Unknown go option: {}", word)),
+        }
+    }
+    Ok(limits)
+}
+
+/// Read a move written as the row then column of the destination
+fn parse_move(m: &str) -> Option<Point> {
+    let digits: Vec<usize> = m
+        .chars()
+        .map(|c| c.to_digit(SIZE as u32).map(|d| d as usize))
+        .collect::<Option<_>>()?;
+    match digits.as_slice() {
+        [row, col] => Point::new(*row, *col).ok(),
+        _ => None,
+    }
+}
+
+fn format_move(Point(row, col): Point) -> String {
+    format!("{}{}", row, col)
+}
+
+/// Write a line of output straight away
+fn send<W: Write>(output: &Mutex<W>, line: &str) {
+    let mut output = output.lock().expect("Not poisoned");
+    let _ = writeln!(output, "{}", line);
+    let _ = output.flush();
+}
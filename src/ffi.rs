@@ -0,0 +1,272 @@
+//! A C API, for embedding the solver in programs written in other languages
+//!
+//! The declarations are in `include/collapsi.h`, generated from this file
+//! with `cbindgen --config cbindgen.toml --output include/collapsi.h`. Link
+//! against the `cdylib` built by `cargo build -r` (`libcollapsi.so` on
+//! Linux).
+//!
+//! A board is an opaque handle created by `collapsi_board_new` or
+//! `collapsi_board_from_deal_id` and released with `collapsi_board_free`.
+//! Functions given a null handle do nothing and report failure. Scores are as
+//! for `Board::best_move_by_cards_remaining`.
+
+use crate::{Board, CollapsiVersion, Point};
+use std::ffi::{CStr, c_char};
+use std::ptr;
+
+/// A position, with its history since it was created
+pub struct CollapsiBoard {
+    board: Board,
+}
+
+/// A square of the grid
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CollapsiSquare {
+    pub row: u8,
+    pub col: u8,
+}
+
+impl From<Point> for CollapsiSquare {
+    fn from(Point(row, col): Point) -> Self {
+        CollapsiSquare {
+            row: row as u8,
+            col: col as u8,
+        }
+    }
+}
+
+/// Read a position from its NUL-terminated notation, for example
+/// "1223/4121r/3123/1b314/0", returning null if it is invalid
+///
+/// # Safety
+///
+/// `notation` must be null or point to a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn collapsi_board_new(notation: *const c_char) -> *mut CollapsiBoard {
+    if notation.is_null() {
+        return ptr::null_mut();
+    }
+    // SAFETY: the caller passes a valid C string
+    let notation = unsafe { CStr::from_ptr(notation) };
+    match notation.to_str().ok().and_then(|s| Board::new(s).ok()) {
+        Some(board) => into_handle(board),
+        None => ptr::null_mut(),
+    }
+}
+
+/// The starting deal with the given ID, under the old rules (v1.1) if `old`
+/// is true, returning null if there is no such deal
+#[unsafe(no_mangle)]
+pub extern "C" fn collapsi_board_from_deal_id(id: u64, old: bool) -> *mut CollapsiBoard {
+    let version = if old {
+        CollapsiVersion::V1_1
+    } else {
+        CollapsiVersion::V1_3
+    };
+    match Board::from_deal_id(version, id) {
+        Ok(board) => into_handle(board),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Release a board
+///
+/// # Safety
+///
+/// `board` must be null or a handle from this API that has not been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn collapsi_board_free(board: *mut CollapsiBoard) {
+    if !board.is_null() {
+        // SAFETY: the handle came from `Box::into_raw` and is freed only once
+        drop(unsafe { Box::from_raw(board) });
+    }
+}
+
+/// Write the notation of a position into `buffer`, NUL-terminated and
+/// truncated to fit `size` bytes
+///
+/// Returns the length of the full notation, not counting the NUL, like
+/// `snprintf`, so a return value of `size` or more means it was truncated.
+///
+/// # Safety
+///
+/// `board` must be null or a live handle, and `buffer` must be null or have
+/// room for `size` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn collapsi_board_notation(
+    board: *const CollapsiBoard,
+    buffer: *mut c_char,
+    size: usize,
+) -> usize {
+    // SAFETY: the caller passes a live handle
+    let Some(board) = (unsafe { board.as_ref() }) else {
+        return 0;
+    };
+    let notation = board.board.notation();
+    if !buffer.is_null() && size > 0 {
+        let length = notation.len().min(size - 1);
+        // SAFETY: the caller's buffer has room for `size` bytes
+        unsafe {
+            ptr::copy_nonoverlapping(notation.as_ptr(), buffer.cast(), length);
+            *buffer.add(length) = 0;
+        }
+    }
+    notation.len()
+}
+
+/// The player to move: 0 for red, 1 for blue, or -1 for a null handle
+///
+/// # Safety
+///
+/// `board` must be null or a live handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn collapsi_board_turn(board: *const CollapsiBoard) -> i32 {
+    // SAFETY: the caller passes a live handle
+    match unsafe { board.as_ref() } {
+        Some(board) => board.board.turn as i32,
+        None => -1,
+    }
+}
+
+/// Write the squares the player to move can move to into `moves`, at most
+/// `capacity` of them
+///
+/// Returns the number of legal moves, which may be more than `capacity`. No
+/// position has more than 16, and 0 means the game is over.
+///
+/// # Safety
+///
+/// `board` must be null or a live handle, and `moves` must be null or have
+/// room for `capacity` squares.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn collapsi_legal_moves(
+    board: *const CollapsiBoard,
+    moves: *mut CollapsiSquare,
+    capacity: usize,
+) -> usize {
+    // SAFETY: the caller passes a live handle
+    let Some(board) = (unsafe { board.as_ref() }) else {
+        return 0;
+    };
+    let legal = board.board.legal_moves();
+    if !moves.is_null() {
+        for (i, &m) in legal.iter().take(capacity).enumerate() {
+            // SAFETY: the caller's buffer has room for `capacity` squares
+            unsafe { *moves.add(i) = m.into() };
+        }
+    }
+    legal.len()
+}
+
+/// Check a path reported for the player to move, for example by a physical
+/// board: `length` squares from the pawn's square to its destination, each a
+/// neighbour of the last (possibly around an edge), covering exactly the
+/// distance on the pawn's card
+///
+/// Returns whether it is a legal path; if so the move can be made with
+/// `collapsi_make_move` to its last square.
+///
+/// # Safety
+///
+/// `board` must be null or a live handle, and `squares` must be null or
+/// point to `length` squares.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn collapsi_check_path(
+    board: *const CollapsiBoard,
+    squares: *const CollapsiSquare,
+    length: usize,
+) -> bool {
+    // SAFETY: the caller passes a live handle
+    let Some(board) = (unsafe { board.as_ref() }) else {
+        return false;
+    };
+    if squares.is_null() {
+        return false;
+    }
+    // SAFETY: the caller's array holds `length` squares
+    let squares = unsafe { std::slice::from_raw_parts(squares, length) };
+    let path: Option<Vec<Point>> = squares
+        .iter()
+        .map(|square| Point::new(square.row as usize, square.col as usize).ok())
+        .collect();
+    path.is_some_and(|path| board.board.check_path(&path).is_ok())
+}
+
+/// Move the player to move to the given square, returning false (and leaving
+/// the board unchanged) if that is not a legal move
+///
+/// # Safety
+///
+/// `board` must be null or a live handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn collapsi_make_move(board: *mut CollapsiBoard, row: u8, col: u8) -> bool {
+    // SAFETY: the caller passes a live handle
+    let Some(board) = (unsafe { board.as_mut() }) else {
+        return false;
+    };
+    match Point::new(row as usize, col as usize) {
+        Ok(point) if board.board.legal_moves().contains(&point) => {
+            board.board.make_move(point);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Take back the last move made since the board was created, returning false
+/// if there is none
+///
+/// # Safety
+///
+/// `board` must be null or a live handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn collapsi_undo_move(board: *mut CollapsiBoard) -> bool {
+    // SAFETY: the caller passes a live handle
+    let Some(board) = (unsafe { board.as_mut() }) else {
+        return false;
+    };
+    if board.board.moves.is_empty() {
+        return false;
+    }
+    board.board.undo_move();
+    true
+}
+
+/// Solve a position with perfect play, writing its score to `score` and a
+/// best move to `best_move`
+///
+/// Returns false if there is no move to make, because the game is over (the
+/// score is still written) or the handle is null. Either output may be null
+/// if it is not wanted.
+///
+/// # Safety
+///
+/// `board` must be null or a live handle, and `best_move` and `score` must
+/// each be null or valid to write to.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn collapsi_solve(
+    board: *mut CollapsiBoard,
+    best_move: *mut CollapsiSquare,
+    score: *mut i8,
+) -> bool {
+    // SAFETY: the caller passes a live handle
+    let Some(board) = (unsafe { board.as_mut() }) else {
+        return false;
+    };
+    let (m, value) = board.board.best_move_by_cards_remaining();
+    // SAFETY: the caller's outputs are valid to write to
+    unsafe {
+        if let Some(score) = score.as_mut() {
+            *score = value;
+        }
+        if let (Some(m), Some(best_move)) = (m, best_move.as_mut()) {
+            *best_move = m.into();
+        }
+    }
+    m.is_some()
+}
+
+fn into_handle(board: Board) -> *mut CollapsiBoard {
+    Box::into_raw(Box::new(CollapsiBoard { board }))
+}
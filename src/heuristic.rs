@@ -0,0 +1,381 @@
+//! Static evaluation and depth-limited search, for positions where searching
+//! to the end of the game is too expensive
+
+use crate::search::{Search, SearchOptions, SearchResult};
+use crate::{Board, Point};
+use std::collections::HashSet;
+
+/// Heuristic scores at or beyond this magnitude are exact game results
+///
+/// A proven result is reported as `WIN` plus the number of cards remaining at
+/// the end of the game, so that it can be converted back to the scale used by
+/// `best_move_by_cards_remaining`.
+pub const WIN: i32 = 1000;
+
+/// Relative importance of each feature in the static evaluation
+///
+/// Each feature is measured as "red minus blue", so a positive score is good
+/// for red (P0) and a negative score is good for blue (P1).
+#[derive(Clone, Debug)]
+pub struct Weights {
+    /// Difference in the number of legal moves available
+    pub mobility: i32,
+
+    /// Difference in the number of face-up cards each pawn could eventually
+    /// walk to
+    pub region: i32,
+
+    /// Bonus for the side to move if an odd number of face-up cards remain
+    pub parity: i32,
+
+    /// Difference in the number of flipped cards adjacent to the opponent
+    pub isolation: i32,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            mobility: 4,
+            region: 2,
+            parity: 1,
+            isolation: 3,
+        }
+    }
+}
+
+/// How a heuristic move compares to perfect play in one position
+pub struct Comparison {
+    /// The move chosen by the depth-limited search (or another method)
+    pub heuristic_move: Point,
+
+    /// The best score achievable with perfect play
+    pub exact_score: i8,
+
+    /// The score achieved by playing `heuristic_move` and then playing
+    /// perfectly
+    pub heuristic_move_score: i8,
+}
+
+impl Comparison {
+    /// Whether the heuristic move is as good as the best move
+    pub fn is_optimal(&self) -> bool {
+        self.heuristic_move_score == self.exact_score
+    }
+
+    /// Whether the heuristic move leads to the same winner as the best move
+    pub fn is_same_outcome(&self) -> bool {
+        self.heuristic_move_score.signum() == self.exact_score.signum()
+    }
+}
+
+/// Convert a heuristic score to the exact cards-remaining score, if it is a
+/// proven result
+pub fn proven_score(score: i32) -> Option<i8> {
+    if score >= WIN {
+        Some((score - WIN) as i8)
+    } else if score <= -WIN {
+        Some((score + WIN) as i8)
+    } else {
+        None
+    }
+}
+
+impl Board {
+    /// Static estimate of how good this position is for red (P0)
+    ///
+    /// Finished games are scored exactly (see `WIN`); otherwise the score is a
+    /// weighted sum of mobility, reachable region size, parity of remaining
+    /// cards, and isolation of each pawn.
+    pub fn evaluate(&self, weights: &Weights) -> i32 {
+        let moves = [self.legal_moves_for(0), self.legal_moves_for(1)];
+        if moves[self.turn].is_empty() {
+            return Self::proven(self.final_score());
+        }
+
+        let mobility = moves[0].len() as i32 - moves[1].len() as i32;
+        let region = self.region_size(0) as i32 - self.region_size(1) as i32;
+        let isolation = self.blocked_neighbors(1) as i32 - self.blocked_neighbors(0) as i32;
+
+        // If every remaining card were used up, who would make the last move?
+        let face_up = self.cards.iter().flatten().filter(|c| **c != 0).count()
+            - self.pawns.iter().filter(|p| self.card(**p) != 0).count();
+        let to_move_sign = if self.turn == 0 { 1 } else { -1 };
+        let parity = if face_up % 2 == 1 {
+            to_move_sign
+        } else {
+            -to_move_sign
+        };
+
+        weights.mobility * mobility
+            + weights.region * region
+            + weights.parity * parity
+            + weights.isolation * isolation
+    }
+
+    /// A good move according to a search of limited depth, falling back on
+    /// `evaluate` when the depth runs out
+    ///
+    /// Return value is the point to which the current player should move (None
+    /// if no possible moves) together with the heuristic score.
+    pub fn best_move_heuristic(&mut self, depth: usize, weights: &Weights) -> (Option<Point>, i32) {
+        self.best_move_heuristic_bounded(
+            depth,
+            weights,
+            None,
+            -2 * WIN,
+            2 * WIN,
+            &mut Search::unlimited(),
+        )
+    }
+
+    /// Like `best_move_heuristic`, but within the given limits
+    ///
+    /// If the search does not complete, the value is as for
+    /// `best_move_by_cards_remaining_limited`: the best of the moves that were
+    /// fully searched, or None if there were none.
+    pub fn best_move_heuristic_limited(
+        &mut self,
+        depth: usize,
+        weights: &Weights,
+        options: SearchOptions,
+    ) -> SearchResult<(Option<Point>, i32)> {
+        let mut search = Search::new(options);
+        let best =
+            self.best_move_heuristic_bounded(depth, weights, None, -2 * WIN, 2 * WIN, &mut search);
+        search.result(best)
+    }
+
+    /// A good move found by searching to depth 1, 2, ... up to `max_depth`
+    ///
+    /// Each iteration tries the previous iteration's best move first, which
+    /// helps alpha-beta pruning. Searching stops early once the result of the
+    /// game is proven.
+    pub fn best_move_iterative_deepening(
+        &mut self,
+        max_depth: usize,
+        weights: &Weights,
+    ) -> (Option<Point>, i32) {
+        let mut best = (None, self.evaluate(weights));
+        for depth in 1..=max_depth {
+            best = self.best_move_heuristic_bounded(
+                depth,
+                weights,
+                best.0,
+                -2 * WIN,
+                2 * WIN,
+                &mut Search::unlimited(),
+            );
+            if proven_score(best.1).is_some() {
+                break;
+            }
+        }
+        best
+    }
+
+    /// Compare the move chosen by a depth-limited search with perfect play
+    ///
+    /// This searches the full game tree several times, so it is only feasible
+    /// on positions the exact solver can handle.
+    pub fn compare_with_exact(&mut self, depth: usize, weights: &Weights) -> Option<Comparison> {
+        let heuristic_move = self.best_move_iterative_deepening(depth, weights).0?;
+        Some(self.compare_move_with_exact(heuristic_move))
+    }
+
+    /// Compare a move chosen by any means with perfect play
+    ///
+    /// `m` must be a legal move in this position.
+    pub fn compare_move_with_exact(&mut self, m: Point) -> Comparison {
+        let (_, exact_score) = self.best_move_by_cards_remaining();
+        self.make_move(m);
+        let (_, heuristic_move_score) = self.best_move_by_cards_remaining();
+        self.undo_move();
+        Comparison {
+            heuristic_move: m,
+            exact_score,
+            heuristic_move_score,
+        }
+    }
+
+    /// Minimax with alpha-beta pruning, cut off at a given depth
+    ///
+    /// This is the recursive function that powers `best_move_heuristic` and
+    /// `best_move_iterative_deepening`. If `first` is a legal move, it is
+    /// searched before the others.
+    fn best_move_heuristic_bounded(
+        &mut self,
+        depth: usize,
+        weights: &Weights,
+        first: Option<Point>,
+        mut at_least: i32,
+        mut at_most: i32,
+        search: &mut Search,
+    ) -> (Option<Point>, i32) {
+        search.visit(self.moves.len());
+        let mut moves: Vec<Point> = self.legal_moves().into_iter().collect();
+        if moves.is_empty() {
+            return (None, Self::proven(self.final_score()));
+        } else if depth == 0 {
+            return (None, self.evaluate(weights));
+        }
+        if let Some(i) = moves.iter().position(|m| Some(*m) == first) {
+            moves[..=i].rotate_right(1);
+        }
+
+        let mut best_score = if self.turn == 0 { -2 * WIN } else { 2 * WIN }; // worst case
+        let mut best_move = moves[0];
+        let mut searched_any = false;
+        for m in moves {
+            if search.aborted() {
+                break;
+            }
+            self.make_move(m); // note: this flips self.turn
+            let (_, score) = self.best_move_heuristic_bounded(
+                depth - 1,
+                weights,
+                None,
+                at_least,
+                at_most,
+                search,
+            );
+            self.undo_move();
+            if search.aborted() {
+                break;
+            }
+            searched_any = true;
+            if self.turn == 0 {
+                if score > best_score {
+                    best_score = score;
+                    best_move = m;
+                    if best_score >= at_most {
+                        break;
+                    }
+                    at_least = at_least.max(best_score);
+                }
+            } else if score < best_score {
+                best_score = score;
+                best_move = m;
+                if best_score <= at_least {
+                    break;
+                }
+                at_most = at_most.min(best_score);
+            }
+        }
+        (searched_any.then_some(best_move), best_score)
+    }
+
+    /// Convert an exact cards-remaining score to the heuristic scale
+    fn proven(score: i8) -> i32 {
+        let score = score as i32;
+        if score > 0 { WIN + score } else { -WIN + score }
+    }
+
+    /// Number of face-up cards the given player's pawn could eventually reach,
+    /// ignoring the distance rules
+    fn region_size(&self, player: usize) -> usize {
+        let mut seen = HashSet::from([self.pawns[player]]);
+        let mut frontier = vec![self.pawns[player]];
+        while let Some(point) = frontier.pop() {
+            for neighbor in point.neighbors() {
+                if self.card(neighbor) != 0 && seen.insert(neighbor) {
+                    frontier.push(neighbor);
+                }
+            }
+        }
+        seen.iter().filter(|p| !self.pawns.contains(p)).count()
+    }
+
+    /// Number of flipped cards next to the given player's pawn
+    fn blocked_neighbors(&self, player: usize) -> usize {
+        self.pawns[player]
+            .neighbors()
+            .iter()
+            .filter(|p| self.card(**p) == 0)
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The example board after some plies of perfect play, small enough to
+    /// search to the end
+    fn late_position(plies: usize) -> Board {
+        let mut board = Board::example();
+        for m in board.perfect_game().into_iter().take(plies) {
+            board.make_move(m);
+        }
+        board
+    }
+
+    #[test]
+    fn deep_search_agrees_with_exact_solver() {
+        let weights = Weights::default();
+        for plies in [6, 8, 10] {
+            let mut board = late_position(plies);
+            let (_, exact) = board.best_move_by_cards_remaining();
+            let (m, score) = board.best_move_heuristic(16, &weights);
+            assert!(m.is_some());
+            assert_eq!(proven_score(score), Some(exact));
+            let comparison = board.compare_with_exact(16, &weights).unwrap();
+            assert_eq!(comparison.exact_score, exact);
+            assert!(comparison.is_optimal());
+            assert!(comparison.is_same_outcome());
+        }
+    }
+
+    #[test]
+    fn finished_games_are_scored_exactly() {
+        let weights = Weights::default();
+        let mut board = late_position(16);
+        assert!(board.legal_moves().is_empty());
+        let (_, exact) = board.best_move_by_cards_remaining();
+        assert_eq!(proven_score(board.evaluate(&weights)), Some(exact));
+        assert_eq!(
+            board.best_move_heuristic(3, &weights),
+            (None, board.evaluate(&weights))
+        );
+        assert!(board.compare_with_exact(3, &weights).is_none());
+    }
+
+    #[test]
+    fn shallow_search_is_no_better_than_perfect_play() {
+        let weights = Weights::default();
+        for plies in 0..4 {
+            let mut board = late_position(plies);
+            let comparison = board.compare_with_exact(1, &weights).unwrap();
+            assert!(board.legal_moves().contains(&comparison.heuristic_move));
+            let (_, exact) = board.best_move_by_cards_remaining();
+            assert_eq!(comparison.exact_score, exact);
+            if board.turn == 0 {
+                assert!(comparison.heuristic_move_score <= exact);
+            } else {
+                assert!(comparison.heuristic_move_score >= exact);
+            }
+            assert_eq!(
+                comparison.is_optimal(),
+                comparison.heuristic_move_score == exact
+            );
+        }
+    }
+
+    #[test]
+    fn limited_search_stops_at_node_limit() {
+        let weights = Weights::default();
+        let mut board = Board::example();
+        let stopped = board.best_move_heuristic_limited(
+            4,
+            &weights,
+            SearchOptions {
+                max_nodes: Some(1),
+                ..Default::default()
+            },
+        );
+        assert!(!stopped.completed);
+        assert_eq!(stopped.value.0, None);
+
+        let full = board.best_move_heuristic_limited(4, &weights, SearchOptions::default());
+        assert!(full.completed);
+        assert_eq!(full.value, board.best_move_heuristic(4, &weights));
+    }
+}
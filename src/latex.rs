@@ -0,0 +1,125 @@
+//! Positions as the LaTeX macros of `paper/paper.tex`, so that its figures can
+//! be generated from solver output
+//!
+//! A position with every card face up is written with `\board`, as in
+//! `\board A223 4A2{\redpawn} 3A23 {\bluepawn}3A4`; otherwise it is written
+//! with `\customboard`, using `\facedown` for the flipped cards. A pawn that
+//! has left its jack is written after the value of its card, as in
+//! `{2\redpawn}`.
+
+use crate::{Board, Point, SIZE};
+use itertools::Itertools;
+
+const PAWNS: [&str; 2] = [r"\redpawn", r"\bluepawn"];
+const PLAYERS: [&str; 2] = ["red", "blue"];
+
+/// How to write the cards
+#[derive(Clone, Debug)]
+pub struct LatexOptions {
+    /// How aces are written: A, as in most of the paper's figures, or 1, as
+    /// in some of them
+    pub ace: char,
+}
+
+impl Default for LatexOptions {
+    fn default() -> Self {
+        LatexOptions { ace: 'A' }
+    }
+}
+
+impl Board {
+    /// This position as a `\board` or `\customboard` macro
+    pub fn to_latex(&self, options: &LatexOptions) -> String {
+        let all_face_up = (0..SIZE)
+            .cartesian_product(0..SIZE)
+            .all(|(x, y)| self.face(Point(x, y)).is_some());
+        if all_face_up {
+            let rows = (0..SIZE).map(|x| {
+                (0..SIZE)
+                    .map(|y| match self.latex_face(Point(x, y), options) {
+                        Some(face) if face.len() == 1 => face,
+                        Some(face) => format!("{{{}}}", face),
+                        None => unreachable!("All cards are face up"),
+                    })
+                    .join("")
+            });
+            format!(r"\board {}", rows.format(" "))
+        } else {
+            let rows = (0..SIZE).map(|x| {
+                (0..SIZE)
+                    .map(|y| match self.latex_face(Point(x, y), options) {
+                        Some(face) => format!(r"{{\card{{{}}}}}", face),
+                        None => r"{\facedown}".to_string(),
+                    })
+                    .join(" ")
+            });
+            format!("\\customboard\n{}", rows.format("\n"))
+        }
+    }
+
+    /// Figures showing each position of a game, starting from this one and
+    /// making the given moves
+    ///
+    /// Each figure is captioned with the move that led to it, and the last
+    /// says who has won if the game is over.
+    pub fn to_latex_figures(
+        &self,
+        moves: &[Point],
+        options: &LatexOptions,
+    ) -> Result<String, &'static str> {
+        let mut board = self.clone();
+        let mut caption = if board.ply() == 0 {
+            "Starting position".to_string()
+        } else {
+            format!("Position after {} plies", board.ply())
+        };
+        let mut figures = vec![];
+        for &m in moves {
+            figures.push(figure(&board.to_latex(options), &caption));
+            if !board.legal_moves().contains(&m) {
+                return Err("Illegal move in the game");
+            }
+            let Point(row, col) = m;
+            caption = format!(
+                "Ply {}: {} moves to $({},{})$",
+                board.ply() + 1,
+                PLAYERS[board.turn],
+                row,
+                col
+            );
+            board.make_move(m);
+        }
+        if board.legal_moves().is_empty() {
+            caption = format!(
+                "{}; {} cannot move, so {} wins",
+                caption,
+                PLAYERS[board.turn],
+                PLAYERS[1 - board.turn]
+            );
+        }
+        figures.push(figure(&board.to_latex(options), &caption));
+        Ok(figures.join("\n\n"))
+    }
+
+    /// The contents of a card in the macros, or None if it is face down
+    fn latex_face(&self, point: Point, options: &LatexOptions) -> Option<String> {
+        let face = match self.face(point)? {
+            'A' => options.ace,
+            face => face,
+        };
+        Some(match self.pawns.iter().position(|pawn| *pawn == point) {
+            Some(player) if face == 'J' => PAWNS[player].to_string(),
+            Some(player) => format!("{}{}", face, PAWNS[player]),
+            None => face.to_string(),
+        })
+    }
+}
+
+/// A figure environment, indented as in the paper
+fn figure(board: &str, caption: &str) -> String {
+    format!(
+        "\\begin{{figure}}[ht]\n  \\centering\n{}\n  \\caption{{{}}}\n\\end{{figure}}",
+        board.lines().map(|line| format!("  {}", line)).join("\n"),
+        caption
+    )
+}
@@ -1,14 +1,46 @@
 use itertools::Itertools;
 use regex::Regex;
 use std::cmp::Eq;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 use std::ops::Add;
 use std::sync::LazyLock;
 
+/// `std::time::Instant` panics in the browser, so use a replacement there
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+pub(crate) use web_time::Instant;
+
+pub mod deals;
+pub mod engine;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod heuristic;
+pub mod latex;
+pub mod mcts;
+pub mod proof;
+pub mod puzzle;
+#[cfg(feature = "python")]
+mod python;
+pub mod render;
+pub mod replay;
+pub mod sample;
+pub mod search;
+#[cfg(feature = "serve")]
+pub mod server;
+pub mod symmetry;
+pub mod tree;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+use deals::Deals;
+use search::Search;
+
 const SIZE: usize = 4;
 
 /// Version of the Collapsi game as published online, supporting v1.1 and v1.3
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CollapsiVersion {
     /// Version 1.1: move 1, 2, 3 or 4 from the starting position
     V1_1,
@@ -17,7 +49,7 @@ pub enum CollapsiVersion {
     V1_3,
 }
 
-static ALL_POINTS: LazyLock<HashSet<Point>> = LazyLock::new(|| {
+static ALL_POINTS: LazyLock<BTreeSet<Point>> = LazyLock::new(|| {
     (0..SIZE)
         .cartesian_product(0..SIZE)
         .map(|(x, y)| Point(x, y))
@@ -25,6 +57,7 @@ static ALL_POINTS: LazyLock<HashSet<Point>> = LazyLock::new(|| {
 });
 
 /// A complete description of the current gamestate
+#[derive(Clone)]
 pub struct Board {
     /// The values of the cards in the 4×4 grid
     ///
@@ -36,9 +69,13 @@ pub struct Board {
     /// The coordinates of the two pawns, red and blue
     pawns: [Point; 2],
 
-    /// The number of plies that have been made so far
+    /// The player to move: 0 for red, 1 for blue
     turn: usize,
 
+    /// The number of plies made before the start of `moves`, for positions
+    /// read part-way through a game
+    start_ply: usize,
+
     /// The history of the game so far, as a list of moves
     ///
     /// A move is a tuple (n, p): moved n spaces starting at point p.
@@ -52,6 +89,7 @@ impl Board {
             cards: [[1, 2, 2, 3], [4, 1, 2, 1], [3, 1, 2, 3], [1, 3, 1, 4]],
             pawns: [Point(1, 3), Point(3, 0)],
             turn: 0,
+            start_ply: 0,
             moves: vec![],
         }
     }
@@ -62,7 +100,7 @@ impl Board {
     /// 1,2,3,4 is how far you can move from that space.
     /// 0 represents a face-down card or a joker in v1.1 (move any distance).
     /// r and b come after the position where the red/blue pawn is located.
-    /// The final number is the number of moves that have occurred.
+    /// The final number is the number of moves that have occurred, at most 16.
     pub fn new(input: &str) -> Result<Board, &str> {
         // Check red and blue pawns appear once each
         if !['r', 'b']
@@ -82,6 +120,15 @@ impl Board {
         match re.captures(input) {
             None => Err("Malformed string"),
             Some(caps) => {
+                let start_ply: usize = caps
+                    .name("turn")
+                    .expect("Turn is required")
+                    .as_str()
+                    .parse()
+                    .expect("Must be digits");
+                if start_ply > SIZE * SIZE {
+                    return Err("At most 16 moves can have occurred");
+                }
                 Ok(Board {
                     cards: caps
                         .iter()
@@ -121,18 +168,31 @@ impl Board {
                         .collect::<Vec<Point>>()
                         .try_into()
                         .expect("Two values, both should be present"),
-                    turn: caps
-                        .name("turn")
-                        .expect("Turn is required")
-                        .as_str()
-                        .parse()
-                        .expect("Must be digits"),
+                    turn: start_ply % 2,
+                    start_ply,
                     moves: vec![],
                 })
             }
         }
     }
 
+    /// This position in the notation read by `Board::new`
+    pub fn notation(&self) -> String {
+        let mut rows = (0..SIZE).map(|x| {
+            (0..SIZE)
+                .map(|y| {
+                    let pawn = match self.pawns.iter().position(|p| *p == Point(x, y)) {
+                        Some(0) => "r",
+                        Some(_) => "b",
+                        None => "",
+                    };
+                    format!("{}{}", self.cards[x][y], pawn)
+                })
+                .join("")
+        });
+        format!("{}/{}", rows.join("/"), self.ply())
+    }
+
     /// Execute this game, with perfect play on both sides
     ///
     /// This board is mutated to the final position, and messages are printed
@@ -165,22 +225,42 @@ impl Board {
         }
     }
 
+    /// The moves of the game from this position with length-perfect play on
+    /// both sides, as chosen by `best_move_by_cards_remaining`
+    pub fn perfect_game(&self) -> Vec<Point> {
+        let mut board = self.clone();
+        let mut moves = vec![];
+        while let (Some(m), _) = board.best_move_by_cards_remaining() {
+            moves.push(m);
+            board.make_move(m);
+        }
+        moves
+    }
+
     /// How many possible games could be played out from this position
     pub fn number_of_possible_games(&mut self) -> u64 {
-        *self.count_game_tree_leaves(&mut 0)
+        *self.count_game_tree_leaves(&mut 0, &mut Search::unlimited())
     }
 
     /// Iterate through the whole game tree, adding to the counter for each leaf
     ///
     /// This is the recursive function that powers `number_of_possible_games`.
-    fn count_game_tree_leaves<'a>(&mut self, counter: &'a mut u64) -> &'a mut u64 {
+    fn count_game_tree_leaves<'a>(
+        &mut self,
+        counter: &'a mut u64,
+        search: &mut Search,
+    ) -> &'a mut u64 {
+        if search.visit(self.moves.len()) {
+            return counter;
+        }
         let moves = self.legal_moves(); //.into_iter().sorted().collect();
         if moves.is_empty() {
+            search.leaf();
             *counter += 1;
         }
         for m in moves {
             self.make_move(m);
-            self.count_game_tree_leaves(counter);
+            self.count_game_tree_leaves(counter, search);
             self.undo_move();
         }
         counter
@@ -195,41 +275,17 @@ impl Board {
     ///
     /// Some boards in the set represent more possible boards than others. Each
     /// board is therefore associated with a relative likelihood value.
+    ///
+    /// This collects every board in memory at once; `Deals` generates them as
+    /// they are needed instead.
     pub fn all_boards(version: CollapsiVersion) -> Vec<(Board, u64)> {
-        let start_value = match version {
-            CollapsiVersion::V1_1 => 0,
-            CollapsiVersion::V1_3 => 1,
-        };
-        let mut boards = vec![];
-        for (pawn2, weight) in [(1, 4), (2, 2), (5, 4), (6, 4), (10, 1)] {
-            for perm in unique_permutations(vec![], &[0, 4, 4, 4, 2]) {
-                let mut cards = vec![start_value]; // first pawn at top-left
-                cards.extend_from_slice(&perm[..pawn2 - 1]);
-                cards.push(start_value); // second pawn in this position
-                cards.extend_from_slice(&perm[pawn2 - 1..]);
-                //println!("{:?}", cards);
-                boards.push((
-                    Board {
-                        cards: [
-                            cards[0..4].try_into().unwrap(),
-                            cards[4..8].try_into().unwrap(),
-                            cards[8..12].try_into().unwrap(),
-                            cards[12..16].try_into().unwrap(),
-                        ],
-                        pawns: [Point(0, 0), Point(pawn2 / 4, pawn2 % 4)],
-                        turn: 0,
-                        moves: vec![],
-                    },
-                    weight,
-                ));
-            }
-        }
+        let deals = Deals::new(version);
         println!(
             "Considering {} boards representing {} deals",
-            boards.len(),
-            boards.iter().map(|(_b, w)| w).sum::<u64>()
+            deals.len(),
+            deals.total_weight()
         );
-        boards
+        deals.iter().collect()
     }
 
     /// The value of the card on the given point
@@ -238,18 +294,54 @@ impl Board {
         self.cards[x][y]
     }
 
+    /// The face of the card on the given point, as drawn in the paper: aces as
+    /// A and the starting jacks as J, or None if it is face down
+    ///
+    /// A pawn is still on its jack until its first move.
+    fn face(&self, point: Point) -> Option<char> {
+        let jack = self
+            .pawns
+            .iter()
+            .enumerate()
+            .any(|(player, pawn)| *pawn == point && self.ply() <= player);
+        match self.card(point) {
+            _ if jack => Some('J'),
+            0 => None,
+            1 => Some('A'),
+            value => char::from_digit(value as u32, 10),
+        }
+    }
+
     /// A winning move from this board state, or None if the position is losing
     ///
     /// This mutates the board in-place when searching, but should return it to
     /// the current position before returning.
     pub fn winning_move(&mut self) -> Option<Point> {
-        for m in self.legal_moves() {
+        self.winning_move_searching(&mut Search::unlimited())
+    }
+
+    /// A winning move, or None if the position is losing or the search was
+    /// stopped
+    ///
+    /// This is the recursive function that powers `winning_move`.
+    fn winning_move_searching(&mut self, search: &mut Search) -> Option<Point> {
+        if search.visit(self.moves.len()) {
+            return None;
+        }
+        let moves = self.legal_moves();
+        if moves.is_empty() {
+            search.leaf();
+        }
+        for m in moves {
             self.make_move(m);
-            if self.winning_move().is_none() {
-                self.undo_move();
+            let reply = self.winning_move_searching(search);
+            self.undo_move();
+            if search.aborted() {
+                return None;
+            }
+            if reply.is_none() {
                 return Some(m);
             }
-            self.undo_move();
         }
         None
     }
@@ -257,9 +349,11 @@ impl Board {
     /// An optimal move in the sense of game-length-perfect play
     ///
     /// Return value is the point to which the current player should move (None
-    /// if no possible moves) together with the expected score.
+    /// if no possible moves) together with the expected score. Scores are
+    /// always from red's point of view: the number of cards remaining when the
+    /// game ends, positive if red wins and negative if blue wins.
     pub fn best_move_by_cards_remaining(&mut self) -> (Option<Point>, i8) {
-        self.best_move_by_cards_remaining_bounded(-16, 16)
+        self.best_move_by_cards_remaining_bounded(-16, 16, &mut Search::unlimited())
     }
 
     /// An optimal move in the sense of game-length-perfect play, but guided by
@@ -268,28 +362,37 @@ impl Board {
     /// This is the recursive function that powers
     /// `best_move_by_cards_remaining`.
     ///
-    /// It is an implementation of minimax with alpha-beta pruning.
+    /// It is an implementation of minimax with alpha-beta pruning. If the
+    /// search is stopped, only the moves searched fully are considered.
     fn best_move_by_cards_remaining_bounded(
         &mut self,
         mut at_least: i8,
         mut at_most: i8,
+        search: &mut Search,
     ) -> (Option<Point>, i8) {
+        search.visit(self.moves.len());
         let moves = self.legal_moves();
         if moves.is_empty() {
-            let cards_remaining = 16 - self.moves.len() as i8;
-            if cards_remaining % 2 == 1 {
-                // P0 wins
-                return (None, cards_remaining);
-            } else {
-                // P1 wins
-                return (None, -cards_remaining);
-            }
+            search.leaf();
+            (None, self.final_score())
         } else {
+            let moves = search.order_moves(self, moves);
             let mut best_score = if self.turn == 0 { -16 } else { 16 }; // worst case
-            let mut best_move = Point(0, 0);
+            let mut best_move = moves[0];
+            let mut cutoff = false;
+            let mut searched_any = false;
             for m in moves {
+                if search.aborted() {
+                    break;
+                }
                 self.make_move(m); // note: this flips self.turn
-                let (_, score) = self.best_move_by_cards_remaining_bounded(at_least, at_most);
+                let (_, score) =
+                    self.best_move_by_cards_remaining_bounded(at_least, at_most, search);
+                if search.aborted() {
+                    self.undo_move();
+                    break;
+                }
+                searched_any = true;
                 if self.turn == 1 {
                     // This was P0's turn
                     if score > best_score {
@@ -297,6 +400,8 @@ impl Board {
                         best_move = m;
                         if best_score >= at_most {
                             self.undo_move();
+                            search.cutoff(self.moves.len());
+                            cutoff = true;
                             break;
                         }
                         if best_score > at_least {
@@ -310,6 +415,8 @@ impl Board {
                         best_move = m;
                         if best_score <= at_least {
                             self.undo_move();
+                            search.cutoff(self.moves.len());
+                            cutoff = true;
                             break;
                         }
                         if best_score < at_most {
@@ -319,19 +426,43 @@ impl Board {
                 }
                 self.undo_move();
             }
-            (Some(best_move), best_score)
+            if !search.aborted() {
+                search.record_best_move(self, best_move, cutoff);
+            }
+            // If the search stopped before any move was fully searched, there
+            // is no best move and the score is only the worst case
+            (searched_any.then_some(best_move), best_score)
         }
     }
 
+    /// The score of a finished game, from red's (P0's) point of view
+    ///
+    /// The winner scores the number of cards remaining on the board.
+    fn final_score(&self) -> i8 {
+        let cards_remaining = 16 - self.ply() as i8;
+        if cards_remaining % 2 == 1 {
+            // P0 wins
+            cards_remaining
+        } else {
+            // P1 wins
+            -cards_remaining
+        }
+    }
+
+    /// The number of plies made since the start of the game
+    pub fn ply(&self) -> usize {
+        self.start_ply + self.moves.len()
+    }
+
     /// Points reachable from `point` in `dist` squares, assuming we already
     /// moved through everything in `visited`
-    fn reachable(&self, point: Point, dist: u8, visited: &mut Vec<Point>) -> HashSet<Point> {
+    fn reachable(&self, point: Point, dist: u8, visited: &mut Vec<Point>) -> BTreeSet<Point> {
         if visited.contains(&point) || self.card(point) == 0 {
-            return HashSet::new();
+            return BTreeSet::new();
         } else if dist == 0 {
-            return HashSet::from([point]);
+            return BTreeSet::from([point]);
         }
-        let mut out = HashSet::new();
+        let mut out = BTreeSet::new();
         for neighbor in point.neighbors() {
             visited.push(point);
             out.extend(self.reachable(neighbor, dist - 1, visited));
@@ -340,9 +471,94 @@ impl Board {
         out
     }
 
+    /// Every path the player to move can take to each legal move, keyed by
+    /// destination
+    ///
+    /// A path covers exactly the distance on the pawn's card without
+    /// revisiting a square or crossing a face-down card, as explored by
+    /// `reachable`. A move from a joker in the old rules is a jump, given as a
+    /// single step that does not wrap.
+    pub fn move_paths(&self) -> BTreeMap<Point, Vec<MovePath>> {
+        let origin = self.pawns[self.turn];
+        let dist = self.card(origin);
+        let mut paths: BTreeMap<Point, Vec<MovePath>> = BTreeMap::new();
+        if dist == 0 {
+            for to in self.legal_moves() {
+                paths.insert(
+                    to,
+                    vec![MovePath {
+                        squares: vec![origin, to],
+                        wraps: vec![false],
+                    }],
+                );
+            }
+            return paths;
+        }
+        let mut found = vec![];
+        self.paths_from(origin, dist, &mut vec![], &mut found);
+        for squares in found {
+            let to = *squares.last().expect("Paths are never empty");
+            // Cannot move onto the opponent's piece
+            if !self.pawns.contains(&to) {
+                paths.entry(to).or_default().push(MovePath::new(squares));
+            }
+        }
+        paths
+    }
+
+    /// The first path found by `move_paths` to `to`, or None if `to` is not
+    /// a legal move
+    pub fn move_path(&self, to: Point) -> Option<MovePath> {
+        self.move_paths().remove(&to)?.into_iter().next()
+    }
+
+    /// Check a path reported for the player to move, for example by a
+    /// physical board, returning the destination if it is valid
+    pub fn check_path(&self, squares: &[Point]) -> Result<Point, &'static str> {
+        match squares {
+            [from, .., to] if *from == self.pawns[self.turn] => self
+                .move_paths()
+                .get(to)
+                .filter(|paths| paths.iter().any(|path| path.squares == squares))
+                .map(|_| *to)
+                .ok_or("Not a legal path"),
+            [_, _, ..] => Err("A path must start at the pawn of the player to move"),
+            _ => Err("A path needs at least two squares"),
+        }
+    }
+
+    /// Add every path from `point` covering exactly `dist` more squares to
+    /// `found`, assuming we already moved through everything in `path`
+    fn paths_from(
+        &self,
+        point: Point,
+        dist: u8,
+        path: &mut Vec<Point>,
+        found: &mut Vec<Vec<Point>>,
+    ) {
+        if path.contains(&point) || self.card(point) == 0 {
+            return;
+        }
+        path.push(point);
+        if dist == 0 {
+            found.push(path.clone());
+        } else {
+            for neighbor in point.neighbors() {
+                self.paths_from(neighbor, dist - 1, path, found);
+            }
+        }
+        path.pop();
+    }
+
     /// All the possible points the current player could move to this ply
-    fn legal_moves(&self) -> HashSet<Point> {
-        let origin: Point = self.pawns[self.turn];
+    fn legal_moves(&self) -> BTreeSet<Point> {
+        self.legal_moves_for(self.turn)
+    }
+
+    /// All the possible points the given player (0 or 1) could move to if it
+    /// were their turn
+    fn legal_moves_for(&self, player: usize) -> BTreeSet<Point> {
+        let origin: Point = self.pawns[player];
         let dist = self.card(origin);
 
         // Special case for starting position in old rules
@@ -392,6 +608,19 @@ impl Board {
         self.set_card(from, dist);
     }
 
+    /// A number uniquely identifying the cards, the pawns and the player to
+    /// move, but not the history
+    pub(crate) fn key(&self) -> u64 {
+        let mut key = self.turn as u64;
+        for Point(x, y) in self.pawns {
+            key = (key << 4) | (x * SIZE + y) as u64;
+        }
+        for card in self.cards.iter().flatten() {
+            key = (key << 3) | *card as u64;
+        }
+        key
+    }
+
     /// Change the given card to have the specified value
     fn set_card(&mut self, point: Point, dist: u8) {
         let Point(x, y) = point;
@@ -459,32 +688,111 @@ impl Point {
     }
 }
 
-/// All ways to arrange cards with the values in `remaining` given the existing
-/// cards in `start`
-///
-/// - `start` is a list of the card values in the first n positions on the
-///   board, with all the other 16-n positions not filled so far.
-/// - `remaining`[i] is the number of cards of value i left to be placed, with i
-///   from 0 (Joker) to 4.
-///
-/// Return value is a list of vectors representing possible boards, where each
-/// one should be exactly 16 long and represent a complete board.
-fn unique_permutations(start: Vec<u8>, remaining: &[u8; 5]) -> Vec<Vec<u8>> {
-    //println!("{:?}", start);
-    let mut out = vec![];
-    for value in 1..remaining.len() {
-        if remaining[value] > 0 {
-            let mut rem_new = remaining.clone();
-            rem_new[value] -= 1;
-            let mut start_new = start.clone();
-            start_new.push(value as u8);
-            for perm in unique_permutations(start_new, &rem_new) {
-                out.push(perm);
+/// A path a pawn can take to make a move, one orthogonal step at a time
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MovePath {
+    /// The squares visited, from the pawn's square to the destination
+    pub squares: Vec<Point>,
+
+    /// Whether each step, between consecutive squares, wraps around an edge
+    /// of the grid to the opposite side
+    pub wraps: Vec<bool>,
+}
+
+impl MovePath {
+    fn new(squares: Vec<Point>) -> MovePath {
+        let wraps = squares
+            .windows(2)
+            .map(|step| {
+                let (Point(x1, y1), Point(x2, y2)) = (step[0], step[1]);
+                x1.abs_diff(x2) == SIZE - 1 || y1.abs_diff(y2) == SIZE - 1
+            })
+            .collect();
+        MovePath { squares, wraps }
+    }
+
+    /// The square the path ends on
+    pub fn destination(&self) -> Point {
+        *self
+            .squares
+            .last()
+            .expect("Paths have at least two squares")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn move_count_in_notation_is_the_starting_ply() {
+        let mut board = Board::new("1220/4121r/3023/1b310/3").unwrap();
+        assert_eq!(board.ply(), 3);
+        assert_eq!(board.turn, 1);
+        assert_eq!(board.notation(), "1220/4121r/3023/1b310/3");
+
+        let m = *board.legal_moves().first().unwrap();
+        board.make_move(m);
+        assert_eq!(board.ply(), 4);
+        assert_eq!(board.turn, 0);
+        assert!(board.notation().ends_with("/4"));
+        board.undo_move();
+        assert_eq!(board.notation(), "1220/4121r/3023/1b310/3");
+
+        assert!(Board::new("1223/4121r/3123/1b314/16").is_ok());
+        assert!(Board::new("1223/4121r/3123/1b314/17").is_err());
+    }
+
+    fn path(squares: &[(usize, usize)]) -> Vec<Point> {
+        squares.iter().map(|&(x, y)| Point(x, y)).collect()
+    }
+
+    #[test]
+    fn several_paths_can_reach_the_same_destination() {
+        // Red's 2 can reach (1,2) through either neighbour
+        let board = Board::new("12r23/4121/3123/1b314/0").unwrap();
+        let paths = board.move_paths();
+        let to_corner: Vec<&[Point]> = paths[&Point(1, 2)]
+            .iter()
+            .map(|path| path.squares.as_slice())
+            .collect();
+        assert_eq!(to_corner.len(), 2);
+        assert!(to_corner.contains(&path(&[(0, 1), (0, 2), (1, 2)]).as_slice()));
+        assert!(to_corner.contains(&path(&[(0, 1), (1, 1), (1, 2)]).as_slice()));
+        for paths in paths.values() {
+            for path in paths {
+                assert_eq!(board.check_path(&path.squares), Ok(path.destination()));
             }
         }
+        let destinations: BTreeSet<Point> = paths.into_keys().collect();
+        assert_eq!(destinations, board.legal_moves());
     }
-    if out.is_empty() {
-        out.push(start);
+
+    #[test]
+    fn check_path_rejects_illegal_paths() {
+        // Red is on a 4 at (1,0), next to a face-down card at (1,3) around
+        // the left edge, and it is red to move
+        let board = Board::new("1223/4r120/3123/1b314/2").unwrap();
+        let valid = path(&[(1, 0), (1, 1), (1, 2), (2, 2), (2, 1)]);
+        assert_eq!(board.check_path(&valid), Ok(Point(2, 1)));
+
+        let revisiting = path(&[(1, 0), (1, 1), (1, 0), (2, 0), (2, 1)]);
+        assert_eq!(board.check_path(&revisiting), Err("Not a legal path"));
+
+        let face_down = path(&[(1, 0), (1, 3), (1, 2), (2, 2), (2, 1)]);
+        assert_eq!(board.check_path(&face_down), Err("Not a legal path"));
+
+        let from_blue = path(&[(3, 0), (3, 1), (2, 1)]);
+        assert_eq!(
+            board.check_path(&from_blue),
+            Err("A path must start at the pawn of the player to move")
+        );
+
+        let too_short = path(&[(1, 0), (1, 1), (2, 1)]);
+        assert_eq!(board.check_path(&too_short), Err("Not a legal path"));
+        assert_eq!(
+            board.check_path(&path(&[(1, 0)])),
+            Err("A path needs at least two squares")
+        );
     }
-    out
 }
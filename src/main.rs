@@ -1,18 +1,52 @@
-// TODO: web interface with WASM
-
+use itertools::Itertools;
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
 use rayon::prelude::*;
 use simple_tqdm::ParTqdm;
 //use simple_tqdm::Tqdm;
 use std::env;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::{Duration, Instant};
 
 use collapsi::Board;
 use collapsi::CollapsiVersion;
+use collapsi::Point;
+use collapsi::deals::Deals;
+use collapsi::engine::Engine;
+use collapsi::heuristic::{self, Weights};
+use collapsi::latex::LatexOptions;
+use collapsi::mcts::{MctsConfig, Playout};
+use collapsi::proof::Proof;
+use collapsi::puzzle::{Puzzle, PuzzleConfig};
+use collapsi::render::RenderOptions;
+use collapsi::replay::Replay;
+use collapsi::sample::Estimate;
+use collapsi::search::{MoveOrdering, SearchOptions, SearchStats};
+use collapsi::server::{Server, ServerConfig};
+use collapsi::symmetry::{Symmetry, WeightCheck};
+use collapsi::tree::{GameTreeCounts, TreeStats};
 
-const USAGE: &str = "Usage: collapsi command board
+const USAGE: &str = "Usage: collapsi command board [options]
 where command is one of:
   solve     (compute a perfect-play move)
   simulate  (run the full game with perfect play, showing all moves)
   full      (explore the full game tree and count the leaves)
+  heuristic (compare a depth-limited heuristic search with perfect play)
+  mcts      (choose a move by Monte Carlo Tree Search and compare with perfect play)
+  ordering  (compare the nodes searched by solve with each move ordering)
+  prove     (prove who wins by proof-number search, and verify the proof)
+  id        (find the ID of a starting deal, after matching it with its representative)
+  deal      (show the starting deal with a given ID, given in place of the board)
+  symmetry  (check that the weights of all boards account for every possible deal)
+  random    (deal random starting boards, with no board argument)
+  puzzle    (find R to play and win puzzles in randomly played games, with no board argument)
+  engine    (follow UCI-style commands on stdin, with no board argument; see src/engine.rs)
+  serve     (answer solver queries over HTTP with JSON, with no board argument; see src/server.rs)
+  render    (draw the board as an SVG image, or PNG if built with the png feature)
+  latex     (write the board with the LaTeX macros of paper/paper.tex)
+  replay    (write an HTML slideshow of the game with perfect play, or the moves given by --trace)
 and board is either the word 'all' or 'all_old' or a string of the form:
   1223/4121r/3123/1b314/0
 where:
@@ -22,31 +56,214 @@ where:
   - r or b indicates that the previous space contains a red/blue pawn
   - the final number is the number of turns taken so far
   - 'all' will instead run the operation for all boards and report a summary
-  - 'all_old' behaves like 'all' but using old rules (Collapsi v1.1)";
+  - 'all_old' behaves like 'all' but using old rules (Collapsi v1.1)
+and options are:
+  --detailed       (full reports game lengths, wins, branching and forced moves over all games)
+  --walk           (full visits every game one by one instead of each distinct position once)
+  --nodes N        (solve or full --walk gives up after visiting N positions)
+  --time-limit MS  (solve or full --walk gives up after MS milliseconds)
+  --stats          (solve or full --walk reports nodes, cutoffs and other search statistics)
+  --ordering NAME  (solve tries moves in this order: natural, mobility, killer, history or tt)
+  --depth N        (search depth for heuristic, default 4, or for puzzle to judge plausible moves, default 2)
+  --every N        (heuristic, mcts, ordering or prove over all boards samples every Nth board, default 1000)
+  --show-proof     (prove prints the proof tree)
+  --old            (deal, random or puzzle uses old rules, Collapsi v1.1)
+  --count N        (random deals N boards or puzzle finds N puzzles, default 1)
+  --plies N        (puzzle wins within N plies, default 5)
+  --difficulty N   (puzzle has at least N losing moves the heuristic rates as highly as the winning move, default 1)
+  --deals N        (puzzle gives up after playing out N random deals, default 10000)
+  --solve          (random also solves each board)
+  --sample N       (solve, full or symmetry over all boards uses N random deals and estimates the results)
+  --solve-every N  (symmetry also compares the scores of every Nth deal checked, default 1000)
+  --iterations N   (mcts iterations per tree, default 10000 unless --time is given)
+  --time MS        (mcts searches for MS milliseconds, stopping sooner if --iterations runs out)
+  --trees N        (mcts trees searched in parallel, default 1)
+  --exploration C  (mcts UCT exploration constant, default 1.414)
+  --seed N         (random seed for mcts, --sample, random or puzzle, default 0 or unseeded for random)
+  --address A      (serve listens on address A, default 127.0.0.1:8080)
+  --timeout MS     (serve gives up searching after MS milliseconds, default 10000)
+  --threads N      (serve handles at most N requests at once, default 4)
+  --cache N        (serve remembers the last N responses, default 10000)
+  -o FILE          (render or replay writes to FILE instead of printing; render writes PNG if it ends in .png)
+  --moves          (render highlights the legal moves)
+  --path SQUARES   (render draws arrows through squares written row then column, e.g. 13,10,11)
+  --scale X        (render scales the image by X, default 1)
+  --trace SQUARES  (latex or replay shows the game with these moves, e.g. 10,20; replay otherwise uses perfect play)
+  --simulate       (latex writes a figure for each position of the game with perfect play)
+  --ace C          (latex writes aces as C, default A, or 1 as in some of the paper's figures)
+  --greedy         (mcts playouts minimise the opponent's moves instead of playing randomly)";
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 3 {
+    let old_rules = args.iter().any(|arg| arg == "--old");
+    let version = if old_rules {
+        CollapsiVersion::V1_1
+    } else {
+        CollapsiVersion::V1_3
+    };
+    match args.get(1).map(|command| command.as_str()) {
+        Some("random") => {
+            run_random(
+                version,
+                option_value(&args, "--seed"),
+                option_value(&args, "--count").unwrap_or(1),
+                args.iter().any(|arg| arg == "--solve"),
+            );
+            return;
+        }
+        Some("puzzle") => {
+            let defaults = PuzzleConfig::default();
+            let config = PuzzleConfig {
+                max_plies: option_value(&args, "--plies").unwrap_or(defaults.max_plies),
+                min_difficulty: option_value(&args, "--difficulty")
+                    .unwrap_or(defaults.min_difficulty),
+                depth: option_value(&args, "--depth").unwrap_or(defaults.depth),
+                version,
+                seed: option_value(&args, "--seed").unwrap_or(defaults.seed),
+                max_deals: option_value(&args, "--deals").unwrap_or(defaults.max_deals),
+                ..defaults
+            };
+            run_puzzle(&config, option_value(&args, "--count").unwrap_or(1));
+            return;
+        }
+        Some("engine") => {
+            Engine::new(std::io::stdout()).run(std::io::stdin().lock());
+            return;
+        }
+        Some("serve") => {
+            let defaults = ServerConfig::default();
+            let config = ServerConfig {
+                address: option_value(&args, "--address").unwrap_or(defaults.address),
+                timeout: option_value(&args, "--timeout")
+                    .map(Duration::from_millis)
+                    .unwrap_or(defaults.timeout),
+                max_concurrent: option_value(&args, "--threads").unwrap_or(defaults.max_concurrent),
+                cache_size: option_value(&args, "--cache").unwrap_or(defaults.cache_size),
+            };
+            run_serve(config);
+            return;
+        }
+        Some("render") if args.len() >= 3 => {
+            let Ok(path) = option_squares(&args, "--path") else {
+                return;
+            };
+            let Ok(output) = checked_option_value(&args, "-o") else {
+                return;
+            };
+            let options = RenderOptions {
+                scale: option_value(&args, "--scale").unwrap_or(1.0),
+                legal_moves: args.iter().any(|arg| arg == "--moves"),
+                path: path.unwrap_or_default(),
+            };
+            run_render(&args[2], &options, output);
+            return;
+        }
+        Some("latex") if args.len() >= 3 => {
+            let Ok(trace) = option_squares(&args, "--trace") else {
+                return;
+            };
+            let options = LatexOptions {
+                ace: option_value(&args, "--ace").unwrap_or('A'),
+            };
+            run_latex(
+                &args[2],
+                &options,
+                trace,
+                args.iter().any(|arg| arg == "--simulate"),
+            );
+            return;
+        }
+        Some("replay") if args.len() >= 3 => {
+            let Ok(trace) = option_squares(&args, "--trace") else {
+                return;
+            };
+            let Ok(output) = checked_option_value(&args, "-o") else {
+                return;
+            };
+            run_replay(&args[2], trace, output);
+            return;
+        }
+        _ => (),
+    }
+    if args.len() < 3 {
         println!("{}", USAGE);
         return;
     }
     let command: &str = &args[1];
     let board: &str = &args[2];
+    let Ok(ordering) = checked_option_value(&args, "--ordering") else {
+        return;
+    };
+    let Ok(max_nodes) = checked_option_value(&args, "--nodes") else {
+        return;
+    };
+    let Ok(time_limit_ms) = checked_option_value(&args, "--time-limit") else {
+        return;
+    };
+    let search_options = SearchOptions {
+        max_nodes,
+        deadline: time_limit_ms.map(|ms| Instant::now() + Duration::from_millis(ms)),
+        stop: None,
+        collect_stats: args.iter().any(|arg| arg == "--stats"),
+        ordering: ordering.unwrap_or_default(),
+        table_size: None,
+    };
+    let depth = option_value(&args, "--depth").unwrap_or(4);
+    let Ok(every) = checked_option_value(&args, "--every") else {
+        return;
+    };
+    let every = every.map_or(1000, NonZeroUsize::get);
+    let defaults = MctsConfig::default();
+    let time_limit = option_value(&args, "--time").map(Duration::from_millis);
+    let mcts_config = MctsConfig {
+        exploration: option_value(&args, "--exploration").unwrap_or(defaults.exploration),
+        playout: if args.iter().any(|arg| arg == "--greedy") {
+            Playout::Greedy
+        } else {
+            Playout::Random
+        },
+        iterations: option_value(&args, "--iterations")
+            .or(defaults.iterations.filter(|_| time_limit.is_none())),
+        time_limit,
+        trees: option_value(&args, "--trees").unwrap_or(defaults.trees),
+        seed: option_value(&args, "--seed").unwrap_or(defaults.seed),
+    };
 
-    if board == "all" || board == "all_old" {
+    if command == "deal" {
+        run_deal(board, version);
+    } else if board == "all" || board == "all_old" {
         let version = if board == "all_old" {
             CollapsiVersion::V1_1
         } else {
             CollapsiVersion::V1_3
         };
+        let sample = option_value(&args, "--sample");
         match command {
-            "solve" => run_solve_all(version),
+            "solve" | "full" if sample.is_some() => run_sample(
+                version,
+                command,
+                sample.expect("Checked above"),
+                mcts_config.seed,
+                args.iter().any(|arg| arg == "--detailed"),
+            ),
+            "solve" => run_solve_all(version, search_options.collect_stats),
+            "full" if args.iter().any(|arg| arg == "--detailed") => run_full_detailed_all(version),
             "full" => run_full_all(version),
+            "heuristic" => run_heuristic_all(version, depth, every),
+            "mcts" => run_mcts_all(version, &mcts_config, every),
+            "ordering" => run_ordering_all(version, every),
+            "prove" => run_prove_all(version, every),
+            "symmetry" => run_symmetry_all(
+                version,
+                sample,
+                option_value(&args, "--solve-every").unwrap_or(1000),
+                mcts_config.seed,
+            ),
             "simulate" => println!("simulate cannot be run over all boards"),
             _ => println!("invalid command"),
         }
     } else {
-        let mut board: Board = match Board::new(&board) {
+        let mut board: Board = match Board::new(board) {
             Ok(board) => board,
             Err(message) => {
                 println!("Invalid board: {}", message);
@@ -55,23 +272,94 @@ fn main() {
         };
         println!("{}", board);
         match command {
-            "solve" => run_solve(&mut board),
+            "solve" => run_solve(&mut board, search_options),
+            "full" if args.iter().any(|arg| arg == "--detailed") => {
+                print_tree_stats(&board.tree_stats())
+            }
+            "full" if args.iter().any(|arg| arg == "--walk") => {
+                run_full_walk(&mut board, search_options)
+            }
             "full" => run_full(&mut board),
             "simulate" => run_simulate(&mut board),
+            "heuristic" => run_heuristic(&mut board, depth),
+            "mcts" => run_mcts(&mut board, &mcts_config),
+            "ordering" => run_ordering(&mut board),
+            "prove" => run_prove(
+                &mut board,
+                search_options,
+                args.iter().any(|arg| arg == "--show-proof"),
+            ),
+            "id" => run_id(&board),
             _ => println!("invalid command"),
         }
     }
 }
 
+/// The value following `name` in the command-line arguments, if present
+fn option_value<T: std::str::FromStr>(args: &[String], name: &str) -> Option<T> {
+    let position = args.iter().position(|arg| arg == name)?;
+    match args.get(position + 1).map(|value| value.parse()) {
+        Some(Ok(value)) => Some(value),
+        _ => {
+            println!("{} requires {}", name, value_description(name));
+            None
+        }
+    }
+}
+
+/// Like `option_value`, but an error if the option is given without a valid
+/// value, for options where falling back on the default would be surprising
+fn checked_option_value<T: std::str::FromStr>(
+    args: &[String],
+    name: &str,
+) -> Result<Option<T>, ()> {
+    let value = option_value(args, name);
+    if value.is_none() && args.iter().any(|arg| arg == name) {
+        Err(())
+    } else {
+        Ok(value)
+    }
+}
+
+/// What the value following an option should be, for error messages
+fn value_description(name: &str) -> &'static str {
+    match name {
+        "-o" => "a file name",
+        "--address" => "an address, like 127.0.0.1:8080",
+        "--ordering" => "one of natural, mobility, killer, history or tt",
+        "--every" => "a number greater than 0",
+        "--ace" => "a single character",
+        _ => "a number",
+    }
+}
+
 // All solutions are using length-perfect play.
 // That is, they call best_move_by_cards_remaining instead of winning_move.
 
-fn run_solve_all(version: CollapsiVersion) {
-    let scores = Board::all_boards(version)
-        .par_iter_mut()
+/// All deals for a version of the game, announcing how many there are
+fn deals(version: CollapsiVersion) -> Deals {
+    let deals = Deals::new(version);
+    println!(
+        "Considering {} boards representing {} deals",
+        deals.len(),
+        deals.total_weight()
+    );
+    deals
+}
+
+fn run_solve_all(version: CollapsiVersion, collect_stats: bool) {
+    let options = SearchOptions {
+        collect_stats,
+        ..Default::default()
+    };
+    let (scores, stats): (Vec<(i8, u64)>, Vec<Option<SearchStats>>) = deals(version)
+        .par_iter()
         .tqdm()
-        .map(|(board, weight)| (board.best_move_by_cards_remaining(), board, weight))
-        .map(|((m, score), board, weight)| {
+        .map(|(mut board, weight)| {
+            let result = board.best_move_by_cards_remaining_limited(options.clone());
+            (result.value, result.stats, board, weight)
+        })
+        .map(|((m, score), stats, board, weight)| {
             if score.unsigned_abs() > 8 {
                 println!("{}", board);
                 println!(
@@ -80,39 +368,654 @@ fn run_solve_all(version: CollapsiVersion) {
                     score
                 );
             }
-            (score, *weight)
+            ((score, weight), stats)
         })
-        .collect::<Vec<(i8, u64)>>()
+        .unzip();
+    let scores = scores
         .into_iter()
         .fold([0; 16], |mut results, (score, weight)| {
             results[score.unsigned_abs() as usize] += weight;
             results
         });
     println!("Scores: {:?}", scores);
+    if let Some(stats) = stats.into_iter().flatten().reduce(SearchStats::merge) {
+        println!("{}", stats);
+    }
+}
+
+/// Estimate the results of `solve` or `full` over all boards from a weighted
+/// random sample of deals
+fn run_sample(version: CollapsiVersion, command: &str, samples: u64, seed: u64, detailed: bool) {
+    let deals = Deals::new(version);
+    println!(
+        "Sampling {} of {} deals with seed {}",
+        samples,
+        deals.total_weight(),
+        seed
+    );
+    let mut rng = StdRng::seed_from_u64(seed);
+    let boards: Vec<Board> = (0..samples).map(|_| deals.sample(&mut rng)).collect();
+    let total = deals.total_weight() as f64;
+    let print_histogram = |name: &str, histograms: &[Vec<u64>]| {
+        println!("{} (estimated total over all deals):", name);
+        for (i, estimate) in Estimate::from_histograms(histograms).iter().enumerate() {
+            println!(
+                "  {:2}: {} ({:.4} per deal)",
+                i,
+                estimate.scale(total),
+                estimate
+            );
+        }
+    };
+    match command {
+        "solve" => {
+            let scores: Vec<i8> = boards
+                .into_par_iter()
+                .tqdm()
+                .map(|mut board| board.best_move_by_cards_remaining().1)
+                .collect();
+            let wins: Vec<f64> = scores.iter().map(|s| (*s > 0) as u8 as f64).collect();
+            println!(
+                "R wins {:.2}% of deals",
+                Estimate::from_samples(&wins).scale(100.0)
+            );
+            let histograms: Vec<Vec<u64>> = scores
+                .into_iter()
+                .map(|score| {
+                    let mut histogram = vec![0; score.unsigned_abs() as usize + 1];
+                    histogram[score.unsigned_abs() as usize] = 1;
+                    histogram
+                })
+                .collect();
+            print_histogram("Deals by score", &histograms);
+        }
+        "full" if detailed => {
+            let stats: Vec<TreeStats> = boards
+                .into_par_iter()
+                .tqdm()
+                .map(|board| board.tree_stats())
+                .collect();
+            let games: Vec<f64> = stats.iter().map(|s| s.games() as f64).collect();
+            let wins: Vec<f64> = stats.iter().map(|s| s.wins[0] as f64).collect();
+            println!(
+                "{} possible games, of which R wins {}",
+                Estimate::from_samples(&games).scale(total),
+                Estimate::from_samples(&wins).scale(total)
+            );
+            let by_length: Vec<Vec<u64>> = stats.into_iter().map(|s| s.games_by_length).collect();
+            print_histogram("Games by length", &by_length);
+        }
+        _ => {
+            let counts: Vec<GameTreeCounts> = boards
+                .into_par_iter()
+                .tqdm()
+                .map(|mut board| board.count_game_tree())
+                .collect();
+            let games: Vec<f64> = counts.iter().map(|c| c.games as f64).collect();
+            let positions: Vec<f64> = counts.iter().map(|c| c.positions as f64).collect();
+            println!(
+                "{} game sequences and {} distinct positions",
+                Estimate::from_samples(&games).scale(total),
+                Estimate::from_samples(&positions).scale(total)
+            );
+            let by_length: Vec<Vec<u64>> = counts
+                .into_iter()
+                .map(|c| c.terminal_positions_by_length)
+                .collect();
+            print_histogram("Distinct finished positions by length", &by_length);
+        }
+    }
 }
 
 fn run_full_all(version: CollapsiVersion) {
-    let mut boards = Board::all_boards(version);
-    let tree_sizes = boards
-        .par_iter_mut()
+    let counts = deals(version)
+        .par_iter()
+        .tqdm()
+        .map(|(mut board, weight)| {
+            GameTreeCounts::default().add_weighted(&board.count_game_tree(), weight)
+        })
+        .reduce(GameTreeCounts::default, |a, b| a.add_weighted(&b, 1));
+    println!("{} game sequences considered in total", counts.games);
+    print_tree_counts(&counts);
+}
+
+fn run_full_detailed_all(version: CollapsiVersion) {
+    let stats = deals(version)
+        .par_iter()
+        .tqdm()
+        .map(|(board, weight)| TreeStats::default().add_weighted(&board.tree_stats(), weight))
+        .reduce(TreeStats::default, |a, b| a.add_weighted(&b, 1));
+    print_tree_stats(&stats);
+}
+
+fn run_heuristic_all(version: CollapsiVersion, depth: usize, every: usize) {
+    let weights = Weights::default();
+    let (optimal, same_outcome, total) = deals(version)
+        .par_iter()
+        .step_by(every)
+        .tqdm()
+        .filter_map(|(mut board, weight)| {
+            board
+                .compare_with_exact(depth, &weights)
+                .map(|comparison| (comparison, weight))
+        })
+        .map(|(comparison, weight)| {
+            (
+                comparison.is_optimal() as u64 * weight,
+                comparison.is_same_outcome() as u64 * weight,
+                weight,
+            )
+        })
+        .reduce(|| (0, 0, 0), |a, b| (a.0 + b.0, a.1 + b.1, a.2 + b.2));
+    println!(
+        "Depth {}: optimal in {:.2}% and same outcome in {:.2}% of sampled deals",
+        depth,
+        100.0 * optimal as f64 / total as f64,
+        100.0 * same_outcome as f64 / total as f64
+    );
+}
+
+fn run_mcts_all(version: CollapsiVersion, config: &MctsConfig, every: usize) {
+    let (optimal, same_outcome, total) = deals(version)
+        .par_iter()
+        .step_by(every)
+        .tqdm()
+        .filter_map(|(mut board, weight)| {
+            board
+                .best_move_mcts(config)
+                .best_move
+                .map(|m| (board.compare_move_with_exact(m), weight))
+        })
+        .map(|(comparison, weight)| {
+            (
+                comparison.is_optimal() as u64 * weight,
+                comparison.is_same_outcome() as u64 * weight,
+                weight,
+            )
+        })
+        .reduce(|| (0, 0, 0), |a, b| (a.0 + b.0, a.1 + b.1, a.2 + b.2));
+    println!(
+        "MCTS: optimal in {:.2}% and same outcome in {:.2}% of sampled deals",
+        100.0 * optimal as f64 / total as f64,
+        100.0 * same_outcome as f64 / total as f64
+    );
+}
+
+fn run_ordering_all(version: CollapsiVersion, every: usize) {
+    let mut boards: Vec<(Board, u64)> = deals(version).iter().step_by(every).collect();
+    for ordering in MoveOrdering::ALL {
+        let options = SearchOptions {
+            ordering,
+            ..Default::default()
+        };
+        let nodes = boards
+            .par_iter_mut()
+            .map(|(board, _)| {
+                board
+                    .best_move_by_cards_remaining_limited(options.clone())
+                    .nodes
+            })
+            .sum::<u64>();
+        println!(
+            "{:?}: {} nodes over {} boards",
+            ordering,
+            nodes,
+            boards.len()
+        );
+    }
+}
+
+fn run_prove_all(version: CollapsiVersion, every: usize) {
+    let (proof_wins, search_wins, invalid, disagreements, total) = deals(version)
+        .par_iter()
+        .step_by(every)
         .tqdm()
-        .map(|(board, weight)| board.number_of_possible_games() * *weight)
-        .sum::<u64>();
-    println!("{} game sequences considered in total", tree_sizes);
+        .map(|(mut board, weight)| {
+            let proof = board
+                .prove(SearchOptions::default())
+                .value
+                .expect("Search has no limits");
+            let valid = proof.verify(&mut board);
+            let (_, score) = board.best_move_by_cards_remaining();
+            let disagreement = (proof.is_win() != (score > 0)).then(|| board.notation());
+            (
+                proof.is_win() as u64 * weight,
+                (score > 0) as u64 * weight,
+                !valid as u64,
+                Vec::from_iter(disagreement),
+                weight,
+            )
+        })
+        .reduce(
+            || (0, 0, 0, vec![], 0),
+            |mut a, b| {
+                a.3.extend(b.3);
+                (a.0 + b.0, a.1 + b.1, a.2 + b.2, a.3, a.4 + b.4)
+            },
+        );
+    println!(
+        "R wins {:.4}% of sampled deals by proof-number search and {:.4}% by alpha-beta search",
+        100.0 * proof_wins as f64 / total as f64,
+        100.0 * search_wins as f64 / total as f64
+    );
+    for notation in &disagreements {
+        println!("The searches disagree on {}", notation);
+    }
+    println!(
+        "{} deals where the searches disagree, {} proofs failed verification",
+        disagreements.len(),
+        invalid
+    );
+    if !disagreements.is_empty() || invalid > 0 {
+        std::process::exit(1);
+    }
+}
+
+fn run_symmetry_all(version: CollapsiVersion, sample: Option<u64>, solve_every: u64, seed: u64) {
+    let deals = Deals::new(version);
+    let hits: Vec<AtomicU8> = (0..deals.len()).map(|_| AtomicU8::new(0)).collect();
+    let check = match sample {
+        None => {
+            println!(
+                "Checking all {} deals with R at the top-left",
+                deals.unreduced_len()
+            );
+            (0..deals.unreduced_len() as usize)
+                .into_par_iter()
+                .tqdm()
+                .map(|i| {
+                    let board = deals.get_unreduced(i as u64).expect("In range");
+                    for (id, _, _) in board.representative_matches() {
+                        hits[id as usize].fetch_add(1, Ordering::Relaxed);
+                    }
+                    board.check_symmetry_weights((i as u64).is_multiple_of(solve_every))
+                })
+                .reduce(WeightCheck::default, WeightCheck::merge)
+        }
+        Some(samples) => {
+            // Random deals: any deal with R at the top-left, moved anywhere
+            let mut rng = StdRng::seed_from_u64(seed);
+            let symmetries: Vec<Symmetry> = Symmetry::all().collect();
+            let boards: Vec<Board> = (0..samples)
+                .map(|_| {
+                    let index = rng.random_range(0..deals.unreduced_len());
+                    let symmetry = symmetries.choose(&mut rng).expect("Not empty");
+                    symmetry.apply_to_board(&deals.get_unreduced(index).expect("In range"))
+                })
+                .collect();
+            boards
+                .par_iter()
+                .enumerate()
+                .tqdm()
+                .map(|(i, board)| {
+                    board.check_symmetry_weights((i as u64).is_multiple_of(solve_every))
+                })
+                .reduce(WeightCheck::default, WeightCheck::merge)
+        }
+    };
+    println!("{}", check);
+    let mut passed = check.passed();
+    if sample.is_none() {
+        let wrong_hits = hits
+            .iter()
+            .filter(|hits| hits.load(Ordering::Relaxed) != 8)
+            .count();
+        println!(
+            "{} representatives are not reached by exactly 8 (deal, symmetry) pairs",
+            wrong_hits
+        );
+        passed &= wrong_hits == 0;
+    }
+    println!(
+        "{}",
+        if passed {
+            "Weights verified"
+        } else {
+            "Weights are WRONG"
+        }
+    );
+}
+
+fn run_random(version: CollapsiVersion, seed: Option<u64>, count: usize, solve: bool) {
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_os_rng(),
+    };
+    for _ in 0..count {
+        let board = Board::random_deal(version, &mut rng);
+        if solve {
+            match board.best_move_via_representative() {
+                Some((Some(m), score)) => {
+                    println!(
+                        "{}  R plays {:?} and gets a score of {}",
+                        board.notation(),
+                        m,
+                        score
+                    )
+                }
+                _ => eprintln!("Something went wrong"),
+            }
+        } else {
+            println!("{}", board.notation());
+        }
+    }
+}
+
+fn run_serve(config: ServerConfig) {
+    match Server::bind(config) {
+        Ok(server) => {
+            match server.local_addr() {
+                Ok(address) => println!("Listening on http://{}", address),
+                Err(message) => println!("Listening: {}", message),
+            }
+            server.run();
+        }
+        Err(message) => println!("Could not start server: {}", message),
+    }
 }
 
-fn run_solve(board: &mut Board) {
-    match board.best_move_by_cards_remaining() {
-        (Some(m), score) => println!("R plays {:?} and gets a score of {}", m, score),
+/// The squares following `name` in the command-line arguments, if present,
+/// written as the row then column and separated by commas
+fn option_squares(args: &[String], name: &str) -> Result<Option<Vec<Point>>, ()> {
+    let Some(position) = args.iter().position(|arg| arg == name) else {
+        return Ok(None);
+    };
+    let squares: Option<Vec<Point>> = args.get(position + 1).and_then(|squares| {
+        squares
+            .split(',')
+            .map(|square| match square.as_bytes() {
+                [row @ b'0'..=b'9', col @ b'0'..=b'9'] => {
+                    Point::new((row - b'0') as usize, (col - b'0') as usize).ok()
+                }
+                _ => None,
+            })
+            .collect()
+    });
+    match squares {
+        Some(squares) => Ok(Some(squares)),
+        None => {
+            println!(
+                "{} requires squares written row then column, like 13,10,11",
+                name
+            );
+            Err(())
+        }
+    }
+}
+
+fn run_render(board: &str, options: &RenderOptions, output: Option<String>) {
+    let board = match Board::new(board) {
+        Ok(board) => board,
+        Err(message) => {
+            println!("Invalid board: {}", message);
+            return;
+        }
+    };
+    let Some(output) = output else {
+        print!("{}", board.to_svg(options));
+        return;
+    };
+    let image = if output.ends_with(".png") {
+        #[cfg(feature = "png")]
+        let image = board.to_png(options);
+        #[cfg(not(feature = "png"))]
+        let image = Err("PNG output needs the png feature: build with --features png");
+        image
+    } else {
+        Ok(board.to_svg(options).into_bytes())
+    };
+    match image.map(|image| std::fs::write(&output, image)) {
+        Ok(Ok(())) => println!("Wrote {}", output),
+        Ok(Err(message)) => println!("Could not write {}: {}", output, message),
+        Err(message) => println!("Could not render: {}", message),
+    }
+}
+
+fn run_latex(board: &str, options: &LatexOptions, trace: Option<Vec<Point>>, simulate: bool) {
+    let board = match Board::new(board) {
+        Ok(board) => board,
+        Err(message) => {
+            println!("Invalid board: {}", message);
+            return;
+        }
+    };
+    let moves = match trace {
+        Some(moves) => moves,
+        None if simulate => board.perfect_game(),
+        None => {
+            println!("{}", board.to_latex(options));
+            return;
+        }
+    };
+    match board.to_latex_figures(&moves, options) {
+        Ok(figures) => println!("{}", figures),
+        Err(message) => println!("{}", message),
+    }
+}
+
+fn run_replay(board: &str, trace: Option<Vec<Point>>, output: Option<String>) {
+    let board = match Board::new(board) {
+        Ok(board) => board,
+        Err(message) => {
+            println!("Invalid board: {}", message);
+            return;
+        }
+    };
+    let replay = match trace {
+        Some(moves) => match Replay::new(&board, moves) {
+            Ok(replay) => replay,
+            Err(message) => {
+                println!("{}", message);
+                return;
+            }
+        },
+        None => Replay::perfect_play(&board),
+    };
+    let html = replay.to_html();
+    match output {
+        Some(output) => match std::fs::write(&output, html) {
+            Ok(()) => println!("Wrote {}", output),
+            Err(message) => println!("Could not write {}: {}", output, message),
+        },
+        None => print!("{}", html),
+    }
+}
+
+fn run_puzzle(config: &PuzzleConfig, count: usize) {
+    let mut found = 0;
+    for puzzle in Puzzle::generate(config).take(count) {
+        println!("{}", puzzle.board);
+        println!("{}", puzzle);
+        found += 1;
+    }
+    if found < count {
+        println!(
+            "Only found {} puzzles in {} deals; try easier requirements or more --deals",
+            found, config.max_deals
+        );
+    }
+}
+
+fn run_solve(board: &mut Board, options: SearchOptions) {
+    // Starting deals are solved via their representative, as in 'solve all'
+    if let Some((canonical, symmetry)) = board.canonical_deal() {
+        let id = canonical.deal_id().expect("Canonical deals have IDs");
+        println!(
+            "Deal ID {}, mapped onto its representative by {}",
+            id, symmetry
+        );
+    }
+    let result = match board.best_move_via_representative_limited(options.clone()) {
+        Some(result) => result,
+        None => board.best_move_by_cards_remaining_limited(options),
+    };
+    match result.value {
+        (Some(m), score) if result.completed => {
+            println!("R plays {:?} and gets a score of {}", m, score)
+        }
+        (Some(m), score) => println!(
+            "Search stopped after {} positions: R's best known move is {:?}, with a provisional score of {}",
+            result.nodes, m, score
+        ),
+        (None, _) if !result.completed => println!(
+            "Search stopped after {} positions, before any move was fully searched",
+            result.nodes
+        ),
         _ => eprintln!("Something went wrong"),
     };
+    if let Some(stats) = result.stats {
+        println!("{}", stats);
+    }
 }
 fn run_full(board: &mut Board) {
+    let counts = board.count_game_tree();
+    println!("{} possible games", counts.games);
+    print_tree_counts(&counts);
+}
+fn print_tree_counts(counts: &GameTreeCounts) {
+    println!("{} distinct positions", counts.positions);
+    println!(
+        "Distinct finished positions by length: {:?}",
+        counts.terminal_positions_by_length
+    );
     println!(
-        "{} positions in game tree",
-        board.number_of_possible_games()
+        "Average branching factor by ply: {}",
+        (0..counts.positions_by_ply.len())
+            .map(|ply| format!("{:.2}", counts.average_branching_factor(ply)))
+            .join(" ")
     );
 }
+fn print_tree_stats(stats: &TreeStats) {
+    println!("{} possible games", stats.games());
+    println!("Games by length: {:?}", stats.games_by_length);
+    println!(
+        "R wins {:.4}% and B wins {:.4}% of games",
+        100.0 * stats.win_fraction(0),
+        100.0 * stats.win_fraction(1)
+    );
+    println!("Positions by number of legal moves, for each ply:");
+    for (ply, branching) in stats.branching_by_ply.iter().enumerate() {
+        println!("  {:2}: {:?}", ply, branching);
+    }
+    println!(
+        "Forced moves: {} positions in the tree, {} distinct",
+        stats.forced_positions, stats.forced_distinct_positions
+    );
+}
+fn run_full_walk(board: &mut Board, options: SearchOptions) {
+    let result = board.number_of_possible_games_limited(options);
+    if result.completed {
+        println!("{} possible games", result.value);
+    } else {
+        println!(
+            "Search stopped after {} positions: at least {} possible games",
+            result.nodes, result.value
+        );
+    }
+    if let Some(stats) = result.stats {
+        println!("{}", stats);
+    }
+}
 fn run_simulate(board: &mut Board) {
     board.simulate_game();
 }
+fn run_heuristic(board: &mut Board, depth: usize) {
+    let weights = Weights::default();
+    match board.best_move_iterative_deepening(depth, &weights) {
+        (Some(m), score) => match heuristic::proven_score(score) {
+            Some(exact) => println!("R plays {:?} and gets a score of {}", m, exact),
+            None => println!("R plays {:?} with a heuristic score of {}", m, score),
+        },
+        _ => eprintln!("Something went wrong"),
+    };
+    if let Some(comparison) = board.compare_with_exact(depth, &weights) {
+        println!(
+            "Perfect play scores {}; the heuristic move scores {}",
+            comparison.exact_score, comparison.heuristic_move_score
+        );
+    }
+}
+fn run_mcts(board: &mut Board, config: &MctsConfig) {
+    let result = board.best_move_mcts(config);
+    for (m, visits, win_rate) in &result.moves {
+        println!("{:?}: {} visits, {:.1}% won", m, visits, 100.0 * win_rate);
+    }
+    match result.best_move {
+        Some(m) => {
+            println!("R plays {:?} after {} iterations", m, result.iterations);
+            let comparison = board.compare_move_with_exact(m);
+            println!(
+                "Perfect play scores {}; the MCTS move scores {}",
+                comparison.exact_score, comparison.heuristic_move_score
+            );
+        }
+        None => eprintln!("Something went wrong"),
+    }
+}
+fn run_ordering(board: &mut Board) {
+    for ordering in MoveOrdering::ALL {
+        let options = SearchOptions {
+            ordering,
+            ..Default::default()
+        };
+        let result = board.best_move_by_cards_remaining_limited(options);
+        println!("{:?}: {} nodes", ordering, result.nodes);
+    }
+}
+fn run_prove(board: &mut Board, options: SearchOptions, show_proof: bool) {
+    let result = board.prove(options);
+    match result.value {
+        Some(proof) => {
+            let winner = if proof.is_win() {
+                "to move"
+            } else {
+                "not to move"
+            };
+            println!(
+                "The player {} wins: proof has {} positions, found after expanding {}",
+                winner,
+                proof.size(),
+                result.nodes
+            );
+            let text = proof.to_string();
+            let reread: Proof = text.parse().expect("Proofs can be read back");
+            if reread == proof && reread.verify(board) {
+                println!("Proof verified");
+            } else {
+                println!("Proof is invalid");
+            }
+            if show_proof {
+                println!("{}", text);
+            }
+        }
+        None => println!("Search stopped after expanding {} positions", result.nodes),
+    }
+}
+fn run_deal(id: &str, version: CollapsiVersion) {
+    let board = match id.parse().map(|id| Board::from_deal_id(version, id)) {
+        Ok(Ok(board)) => board,
+        Ok(Err(message)) => {
+            println!("Invalid deal ID: {}", message);
+            return;
+        }
+        Err(_) => {
+            println!("Deal IDs must be numbers");
+            return;
+        }
+    };
+    println!("{}", board);
+    println!("{}", board.notation());
+}
+fn run_id(board: &Board) {
+    match board.canonical_deal() {
+        Some((canonical, symmetry)) => {
+            if canonical.notation() != board.notation() {
+                println!("Equivalent to:\n{}", canonical);
+                println!("by {}", symmetry);
+            }
+            let id = canonical.deal_id().expect("Canonical deals have IDs");
+            println!("Deal ID: {}", id);
+        }
+        None => println!("Not a legal starting deal"),
+    }
+}
@@ -0,0 +1,305 @@
+//! Monte Carlo Tree Search, for positions where exact search is infeasible
+
+use crate::{Board, Instant, Point};
+use itertools::Itertools;
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Iterations per tree when neither `iterations` nor `time_limit` is set
+const DEFAULT_ITERATIONS: u64 = 10_000;
+
+/// How moves are chosen when playing out a game from a new tree node
+#[derive(Clone, Copy, Debug)]
+pub enum Playout {
+    /// Choose uniformly at random from the legal moves
+    Random,
+
+    /// Choose the move that leaves the opponent with the fewest legal moves,
+    /// breaking ties at random
+    Greedy,
+}
+
+/// Settings for a Monte Carlo Tree Search
+#[derive(Clone, Debug)]
+pub struct MctsConfig {
+    /// The constant c in the UCT formula w/n + c √(ln N / n)
+    pub exploration: f64,
+
+    /// How to play out games from newly expanded nodes
+    pub playout: Playout,
+
+    /// Stop after this many iterations per tree
+    ///
+    /// If neither this nor `time_limit` is set, each tree stops after 10,000
+    /// iterations.
+    pub iterations: Option<u64>,
+
+    /// Stop after this much time has passed
+    pub time_limit: Option<Duration>,
+
+    /// Number of independent trees to grow in parallel (root parallelisation)
+    pub trees: usize,
+
+    /// Seed for the random number generator of the first tree; tree i uses
+    /// `seed + i`
+    pub seed: u64,
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        MctsConfig {
+            exploration: std::f64::consts::SQRT_2,
+            playout: Playout::Random,
+            iterations: Some(DEFAULT_ITERATIONS),
+            time_limit: None,
+            trees: 1,
+            seed: 0,
+        }
+    }
+}
+
+/// The outcome of a Monte Carlo Tree Search
+#[derive(Debug)]
+pub struct MctsResult {
+    /// The most-visited move, or None if there are no legal moves
+    pub best_move: Option<Point>,
+
+    /// Each legal move from the root, with its visit count and the fraction of
+    /// those visits won by the current player, most-visited first
+    pub moves: Vec<(Point, u64, f64)>,
+
+    /// Total iterations over all trees
+    pub iterations: u64,
+}
+
+/// Visits and wins for each move from the root of one tree
+type RootStats = HashMap<Point, (u64, u64)>;
+
+/// A node in the search tree, stored in a flat list and referred to by index
+struct Node {
+    /// The move that led to this node (None at the root)
+    point: Option<Point>,
+
+    /// The player (0 or 1) who made that move
+    player: usize,
+
+    parent: Option<usize>,
+    children: Vec<usize>,
+
+    /// Legal moves from this node that do not yet have a child
+    untried: Vec<Point>,
+
+    visits: u64,
+
+    /// Number of playouts through this node won by `player`
+    wins: u64,
+}
+
+impl Board {
+    /// A move chosen by Monte Carlo Tree Search using UCT
+    ///
+    /// If `config.trees` is more than 1, several trees are searched in
+    /// parallel (if the `parallel` feature is enabled) with different seeds and
+    /// their root statistics are summed.
+    pub fn best_move_mcts(&self, config: &MctsConfig) -> MctsResult {
+        let seeds = config.seed..config.seed + config.trees.max(1) as u64;
+        #[cfg(feature = "parallel")]
+        let seeds = seeds.into_par_iter();
+        let trees: Vec<(RootStats, u64)> = seeds
+            .map(|seed| self.clone().mcts_tree(config, seed))
+            .collect();
+
+        let mut totals: HashMap<Point, (u64, u64)> = HashMap::new();
+        let mut iterations = 0;
+        for (root_stats, tree_iterations) in trees {
+            iterations += tree_iterations;
+            for (point, (visits, wins)) in root_stats {
+                let total = totals.entry(point).or_default();
+                total.0 += visits;
+                total.1 += wins;
+            }
+        }
+        let moves: Vec<(Point, u64, f64)> = totals
+            .into_iter()
+            .map(|(point, (visits, wins))| (point, visits, wins as f64 / visits.max(1) as f64))
+            .sorted_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0))) // most visited first
+            .collect();
+        MctsResult {
+            best_move: moves.first().map(|(point, _, _)| *point),
+            moves,
+            iterations,
+        }
+    }
+
+    /// Grow a single search tree, returning the visits and wins of each root
+    /// move, and the number of iterations carried out
+    fn mcts_tree(&mut self, config: &MctsConfig, seed: u64) -> (RootStats, u64) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let start = Instant::now();
+        let mut tree = vec![Node {
+            point: None,
+            player: 1 - self.turn,
+            parent: None,
+            children: vec![],
+            untried: self.legal_moves().into_iter().collect(),
+            visits: 0,
+            wins: 0,
+        }];
+
+        let iteration_limit = match (config.iterations, config.time_limit) {
+            (None, None) => Some(DEFAULT_ITERATIONS),
+            (limit, _) => limit,
+        };
+        let mut iterations = 0;
+        while iteration_limit.is_none_or(|limit| iterations < limit)
+            && config
+                .time_limit
+                .is_none_or(|limit| start.elapsed() < limit)
+        {
+            iterations += 1;
+            let mut depth = 0;
+
+            // Selection
+            let mut node = 0;
+            while tree[node].untried.is_empty() && !tree[node].children.is_empty() {
+                node = uct_child(&tree, node, config.exploration);
+                self.make_move(tree[node].point.expect("Only the root has no move"));
+                depth += 1;
+            }
+
+            // Expansion
+            if !tree[node].untried.is_empty() {
+                let i = rng.random_range(0..tree[node].untried.len());
+                let point = tree[node].untried.swap_remove(i);
+                let player = self.turn;
+                self.make_move(point);
+                depth += 1;
+                tree.push(Node {
+                    point: Some(point),
+                    player,
+                    parent: Some(node),
+                    children: vec![],
+                    untried: self.legal_moves().into_iter().collect(),
+                    visits: 0,
+                    wins: 0,
+                });
+                let child = tree.len() - 1;
+                tree[node].children.push(child);
+                node = child;
+            }
+
+            // Playout
+            let winner = loop {
+                let moves: Vec<Point> = self.legal_moves().into_iter().collect();
+                let choice = match config.playout {
+                    Playout::Random => moves.choose(&mut rng).copied(),
+                    Playout::Greedy => self.greedy_move(&moves, &mut rng),
+                };
+                match choice {
+                    Some(point) => {
+                        self.make_move(point);
+                        depth += 1;
+                    }
+                    None => break 1 - self.turn,
+                }
+            };
+            for _ in 0..depth {
+                self.undo_move();
+            }
+
+            // Backpropagation
+            let mut current = Some(node);
+            while let Some(i) = current {
+                tree[i].visits += 1;
+                if tree[i].player == winner {
+                    tree[i].wins += 1;
+                }
+                current = tree[i].parent;
+            }
+        }
+
+        let root_stats = tree[0]
+            .children
+            .iter()
+            .map(|&child| {
+                let node = &tree[child];
+                (
+                    node.point.expect("Children have moves"),
+                    (node.visits, node.wins),
+                )
+            })
+            .collect();
+        (root_stats, iterations)
+    }
+
+    /// The move that leaves the opponent with the fewest replies
+    fn greedy_move(&mut self, moves: &[Point], rng: &mut StdRng) -> Option<Point> {
+        let replies: Vec<usize> = moves
+            .iter()
+            .map(|point| {
+                self.make_move(*point);
+                let replies = self.legal_moves().len();
+                self.undo_move();
+                replies
+            })
+            .collect();
+        let fewest = replies.iter().min()?;
+        let best: Vec<Point> = moves
+            .iter()
+            .zip(&replies)
+            .filter(|(_, r)| *r == fewest)
+            .map(|(point, _)| *point)
+            .collect();
+        best.choose(rng).copied()
+    }
+}
+
+/// The child of `node` that maximises the UCT formula
+fn uct_child(tree: &[Node], node: usize, exploration: f64) -> usize {
+    let log_parent = (tree[node].visits as f64).ln();
+    let uct = |child: &usize| {
+        let child = &tree[*child];
+        let visits = child.visits as f64;
+        child.wins as f64 / visits + exploration * (log_parent / visits).sqrt()
+    };
+    *tree[node]
+        .children
+        .iter()
+        .max_by(|a, b| uct(a).total_cmp(&uct(b)))
+        .expect("Only called on nodes with children")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_without_limits_uses_default_iterations() {
+        let config = MctsConfig {
+            iterations: None,
+            time_limit: None,
+            ..Default::default()
+        };
+        let result = Board::example().best_move_mcts(&config);
+        assert_eq!(result.iterations, DEFAULT_ITERATIONS);
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn search_stops_at_time_limit() {
+        let config = MctsConfig {
+            iterations: None,
+            time_limit: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let result = Board::example().best_move_mcts(&config);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(result.iterations > 0);
+    }
+}
@@ -0,0 +1,263 @@
+//! Proof-number search for the win/loss question, producing a proof tree that
+//! can be checked independently of the search
+
+use crate::search::{Search, SearchOptions, SearchResult};
+use crate::{Board, Point, SIZE};
+use std::fmt;
+use std::str::FromStr;
+
+/// Proof and disproof numbers at or above this are infinite
+const INFINITY: u32 = u32::MAX;
+
+/// A certificate of the result of a position, from the point of view of the
+/// player to move
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Proof {
+    /// The player to move wins by moving to this point, after which the
+    /// opponent is lost
+    Win(Point, Box<Proof>),
+
+    /// The player to move loses: after each of their legal moves (possibly
+    /// none) the opponent wins
+    Loss(Vec<(Point, Proof)>),
+}
+
+impl Proof {
+    /// Whether the player to move wins
+    pub fn is_win(&self) -> bool {
+        matches!(self, Proof::Win(_, _))
+    }
+
+    /// Number of positions in the proof tree
+    pub fn size(&self) -> usize {
+        match self {
+            Proof::Win(_, child) => 1 + child.size(),
+            Proof::Loss(children) => 1 + children.iter().map(|(_, c)| c.size()).sum::<usize>(),
+        }
+    }
+
+    /// Check that this is a valid proof for the given position
+    ///
+    /// This only uses the rules of the game, not any search. The board is
+    /// returned to its current position afterwards.
+    pub fn verify(&self, board: &mut Board) -> bool {
+        match self {
+            Proof::Win(point, child) => {
+                if !board.legal_moves().contains(point) || child.is_win() {
+                    return false;
+                }
+                board.make_move(*point);
+                let valid = child.verify(board);
+                board.undo_move();
+                valid
+            }
+            Proof::Loss(children) => {
+                let moves: Vec<Point> = board.legal_moves().into_iter().collect();
+                let mut covered: Vec<Point> = children.iter().map(|(point, _)| *point).collect();
+                covered.sort();
+                if covered != moves {
+                    return false;
+                }
+                children.iter().all(|(point, child)| {
+                    board.make_move(*point);
+                    let valid = child.is_win() && child.verify(board);
+                    board.undo_move();
+                    valid
+                })
+            }
+        }
+    }
+}
+
+/// Compact text form of a proof
+///
+/// A win is written `+xy` followed by the opponent's lost position, where `xy`
+/// is the row and column of the move. A loss is written `-(...)` containing
+/// `xy` followed by the opponent's won position for each legal move. For
+/// example, `+12-()` means "move to (1, 2), after which the opponent cannot
+/// move".
+impl fmt::Display for Proof {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Proof::Win(Point(x, y), child) => write!(f, "+{}{}{}", x, y, child),
+            Proof::Loss(children) => {
+                write!(f, "-(")?;
+                for (Point(x, y), child) in children {
+                    write!(f, "{}{}{}", x, y, child)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl FromStr for Proof {
+    type Err = &'static str;
+
+    /// Read a proof written by `Display`
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut chars = input.chars().peekable();
+        let proof = parse_proof(&mut chars)?;
+        match chars.next() {
+            None => Ok(proof),
+            Some(_) => Err("Unexpected characters after proof"),
+        }
+    }
+}
+
+/// Read one proof from the front of `chars`
+fn parse_proof(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<Proof, &'static str> {
+    match chars.next() {
+        Some('+') => {
+            let point = parse_point(chars)?;
+            Ok(Proof::Win(point, Box::new(parse_proof(chars)?)))
+        }
+        Some('-') => {
+            if chars.next() != Some('(') {
+                return Err("Expected ( after -");
+            }
+            let mut children = vec![];
+            while chars.peek().is_some_and(|c| *c != ')') {
+                let point = parse_point(chars)?;
+                children.push((point, parse_proof(chars)?));
+            }
+            match chars.next() {
+                Some(')') => Ok(Proof::Loss(children)),
+                _ => Err("Expected )"),
+            }
+        }
+        _ => Err("Expected + or -"),
+    }
+}
+
+/// Read a two-digit point from the front of `chars`
+fn parse_point(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<Point, &'static str> {
+    let mut digit = || {
+        chars
+            .next()
+            .and_then(|c| c.to_digit(SIZE as u32))
+            .ok_or("Expected a coordinate")
+    };
+    let x = digit()?;
+    let y = digit()?;
+    Ok(Point(x as usize, y as usize))
+}
+
+/// A position in the search tree, stored in a flat list and referred to by
+/// index
+///
+/// Proof and disproof numbers are from the point of view of the player to
+/// move: `proof` is the number of positions that must be expanded to show they
+/// win, and `disproof` the number to show they lose.
+struct Node {
+    /// The move that led to this node (None at the root)
+    point: Option<Point>,
+
+    parent: Option<usize>,
+    children: Vec<usize>,
+    expanded: bool,
+    proof: u32,
+    disproof: u32,
+}
+
+impl Board {
+    /// Whether the player to move wins, with a proof, found by proof-number
+    /// search
+    ///
+    /// If the search does not complete within the limits, the value is None.
+    pub fn prove(&mut self, options: SearchOptions) -> SearchResult<Option<Proof>> {
+        let mut search = Search::new(options);
+        let mut tree = vec![Node {
+            point: None,
+            parent: None,
+            children: vec![],
+            expanded: false,
+            proof: 1,
+            disproof: 1,
+        }];
+
+        while tree[0].proof != 0 && tree[0].disproof != 0 {
+            // Descend to the most-proving node
+            let mut node = 0;
+            let mut depth = 0;
+            while tree[node].expanded {
+                node = *tree[node]
+                    .children
+                    .iter()
+                    .min_by_key(|child| tree[**child].disproof)
+                    .expect("Expanded nodes that are not proven have children");
+                self.make_move(tree[node].point.expect("Only the root has no move"));
+                depth += 1;
+            }
+
+            // Expand it
+            if search.visit(self.moves.len()) {
+                for _ in 0..depth {
+                    self.undo_move();
+                }
+                return search.result(None);
+            }
+            let moves = self.legal_moves();
+            if moves.is_empty() {
+                search.leaf();
+            }
+            for point in moves {
+                tree.push(Node {
+                    point: Some(point),
+                    parent: Some(node),
+                    children: vec![],
+                    expanded: false,
+                    proof: 1,
+                    disproof: 1,
+                });
+                let child = tree.len() - 1;
+                tree[node].children.push(child);
+            }
+            tree[node].expanded = true;
+            for _ in 0..depth {
+                self.undo_move();
+            }
+
+            // Update the numbers back up to the root
+            let mut current = Some(node);
+            while let Some(i) = current {
+                let children = &tree[i].children;
+                let proof = children
+                    .iter()
+                    .map(|c| tree[*c].disproof)
+                    .min()
+                    .unwrap_or(INFINITY);
+                let disproof = children
+                    .iter()
+                    .map(|c| tree[*c].proof)
+                    .fold(0, u32::saturating_add);
+                tree[i].proof = proof;
+                tree[i].disproof = disproof;
+                current = tree[i].parent;
+            }
+        }
+
+        search.result(Some(extract_proof(&tree, 0)))
+    }
+}
+
+/// Read off the proof of a solved node
+fn extract_proof(tree: &[Node], node: usize) -> Proof {
+    let point = |child: usize| tree[child].point.expect("Only the root has no move");
+    if tree[node].proof == 0 {
+        let child = *tree[node]
+            .children
+            .iter()
+            .find(|c| tree[**c].disproof == 0)
+            .expect("A won position has a lost child");
+        Proof::Win(point(child), Box::new(extract_proof(tree, child)))
+    } else {
+        let mut children: Vec<(Point, Proof)> = tree[node]
+            .children
+            .iter()
+            .map(|c| (point(*c), extract_proof(tree, *c)))
+            .collect();
+        children.sort_by_key(|(point, _)| *point);
+        Proof::Loss(children)
+    }
+}
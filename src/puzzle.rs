@@ -0,0 +1,216 @@
+//! "Red to play and win" puzzles, found in positions reached by random play
+
+use crate::heuristic::Weights;
+use crate::{Board, CollapsiVersion, Point, SIZE};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::IteratorRandom;
+use std::collections::HashSet;
+use std::fmt;
+
+/// What makes a position a good puzzle
+#[derive(Clone, Debug)]
+pub struct PuzzleConfig {
+    /// Longest forced win allowed, in plies from the puzzle position (counting
+    /// both players' moves)
+    pub max_plies: usize,
+
+    /// Fewest losing moves that must look plausible (see `Puzzle::difficulty`)
+    pub min_difficulty: usize,
+
+    /// Depth of the heuristic search used to judge whether a move looks
+    /// plausible
+    pub depth: usize,
+
+    /// Weights of the heuristic search used to judge whether a move looks
+    /// plausible
+    pub weights: Weights,
+
+    /// Rules of the game to deal with
+    pub version: CollapsiVersion,
+
+    /// Random seed for dealing and playing to the puzzle positions
+    pub seed: u64,
+
+    /// Most random deals to play out before giving up, since some
+    /// requirements are rarely or never met
+    pub max_deals: usize,
+}
+
+impl Default for PuzzleConfig {
+    fn default() -> Self {
+        PuzzleConfig {
+            max_plies: 5,
+            min_difficulty: 1,
+            depth: 2,
+            weights: Weights::default(),
+            version: CollapsiVersion::V1_3,
+            seed: 0,
+            max_deals: 10_000,
+        }
+    }
+}
+
+/// A position where red has exactly one winning move
+#[derive(Clone)]
+pub struct Puzzle {
+    /// The position, with red to play
+    pub board: Board,
+
+    /// Red's winning move followed by perfect play from both sides, where red
+    /// wins as quickly as possible and blue holds out as long as possible
+    pub solution: Vec<Point>,
+
+    /// The number of cards remaining at the end of the solution
+    pub score: i8,
+
+    /// Number of losing moves that the heuristic search rates at least as
+    /// highly as the winning move
+    pub difficulty: usize,
+}
+
+impl fmt::Display for Puzzle {
+    /// The position in board notation, then the solution line
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.board.notation())?;
+        write!(
+            f,
+            "R to play and win in {} plies (difficulty {}):",
+            self.solution.len(),
+            self.difficulty
+        )?;
+        for (i, m) in self.solution.iter().enumerate() {
+            let player = if i % 2 == 0 { "R" } else { "B" };
+            write!(f, " {} {:?}", player, m)?;
+        }
+        Ok(())
+    }
+}
+
+impl Puzzle {
+    /// Distinct puzzles meeting the requirements, found in up to
+    /// `config.max_deals` random deals
+    ///
+    /// Each random deal is played out at random, and the positions with red to
+    /// play along the way are tried as puzzles, latest first. The iterator
+    /// ends once every deal has been tried.
+    pub fn generate(config: &PuzzleConfig) -> impl Iterator<Item = Puzzle> + '_ {
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let mut seen = HashSet::new();
+        (0..config.max_deals).flat_map(move |_| {
+            let mut board = Board::random_deal(config.version, &mut rng);
+            while let Some(m) = board.legal_moves().into_iter().choose(&mut rng) {
+                board.make_move(m);
+            }
+            let mut found = vec![];
+            while !board.moves.is_empty() {
+                board.undo_move();
+                if board.turn == 0
+                    && seen.insert(board.key())
+                    && let Some(puzzle) = board.puzzle(config)
+                {
+                    found.push(puzzle);
+                }
+            }
+            found
+        })
+    }
+}
+
+impl Board {
+    /// This position as a puzzle, or None if it does not meet the
+    /// requirements
+    ///
+    /// Red must be to play and have at least two legal moves, exactly one of
+    /// which wins, and it must win within `config.max_plies`.
+    pub fn puzzle(&mut self, config: &PuzzleConfig) -> Option<Puzzle> {
+        if self.turn != 0 {
+            return None;
+        }
+        let moves = self.legal_moves();
+        if moves.len() < 2 {
+            return None;
+        }
+
+        // Exact and heuristic scores after each move
+        let mut winning = None;
+        let mut losing = vec![];
+        for m in moves {
+            self.make_move(m);
+            let (_, score) = self.best_move_by_cards_remaining();
+            let (_, heuristic) = self.best_move_heuristic(config.depth, &config.weights);
+            self.undo_move();
+            if score > 0 {
+                if winning.is_some() {
+                    return None;
+                }
+                winning = Some((m, score, heuristic));
+            } else {
+                losing.push(heuristic);
+            }
+        }
+        let (m, score, heuristic) = winning?;
+        if SIZE * SIZE - self.ply() - score as usize > config.max_plies {
+            return None;
+        }
+        let difficulty = losing.iter().filter(|h| **h >= heuristic).count();
+        if difficulty < config.min_difficulty {
+            return None;
+        }
+
+        // Play out the solution
+        let mut solution = vec![m];
+        self.make_move(m);
+        while let (Some(reply), _) = self.best_move_by_cards_remaining() {
+            solution.push(reply);
+            self.make_move(reply);
+        }
+        for _ in &solution {
+            self.undo_move();
+        }
+        Some(Puzzle {
+            board: Board::new(&self.notation()).expect("Notation can be read back"),
+            solution,
+            score,
+            difficulty,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn puzzles_meet_the_requirements() {
+        let config = PuzzleConfig {
+            max_deals: 20,
+            ..Default::default()
+        };
+        let puzzles: Vec<Puzzle> = Puzzle::generate(&config).take(2).collect();
+        assert!(!puzzles.is_empty());
+        for puzzle in puzzles {
+            let mut board = puzzle.board.clone();
+            assert_eq!(board.turn, 0);
+            assert!(puzzle.solution.len() <= config.max_plies);
+            assert!(puzzle.difficulty >= config.min_difficulty);
+            for m in &puzzle.solution {
+                assert!(board.legal_moves().contains(m));
+                board.make_move(*m);
+            }
+            assert!(board.legal_moves().is_empty());
+            assert_eq!(board.final_score(), puzzle.score);
+            assert!(puzzle.score > 0);
+        }
+    }
+
+    #[test]
+    fn impossible_requirements_give_up() {
+        let config = PuzzleConfig {
+            min_difficulty: SIZE * SIZE,
+            max_deals: 5,
+            ..Default::default()
+        };
+        assert_eq!(Puzzle::generate(&config).count(), 0);
+    }
+}
@@ -0,0 +1,358 @@
+//! Python bindings, for exploring positions and deals interactively
+//!
+//! Build with `maturin develop --release` (see pyproject.toml), then
+//! `import collapsi`. Scores are as for `Board::best_move_by_cards_remaining`,
+//! and squares are `(row, col)` tuples.
+
+use crate::deals::Deals;
+use crate::{Board, CollapsiVersion, Point, SIZE};
+use numpy::ndarray::{Array1, Array3, Axis};
+use numpy::{IntoPyArray, PyArray1, PyArray2, PyReadonlyArray1};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// A position, with its history since it was created
+#[pyclass(name = "Board")]
+struct PyBoard {
+    board: Board,
+}
+
+#[pymethods]
+impl PyBoard {
+    /// Read a position from its notation, for example "1223/4121r/3123/1b314/0"
+    #[new]
+    fn new(notation: &str) -> PyResult<Self> {
+        let board =
+            Board::new(notation).map_err(|message| PyValueError::new_err(message.to_string()))?;
+        Ok(PyBoard { board })
+    }
+
+    /// The starting deal with the given ID
+    #[staticmethod]
+    #[pyo3(signature = (id, old = false))]
+    fn from_deal_id(id: u64, old: bool) -> PyResult<Self> {
+        let board = Board::from_deal_id(version(old), id).map_err(PyValueError::new_err)?;
+        Ok(PyBoard { board })
+    }
+
+    fn notation(&self) -> String {
+        self.board.notation()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Board('{}')", self.board.notation())
+    }
+
+    fn __str__(&self) -> String {
+        self.board.to_string()
+    }
+
+    /// Card values as a 4×4 array, with 0 for a flipped card (or a joker in
+    /// the old rules)
+    #[getter]
+    fn cards<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<u8>> {
+        cards_array(std::slice::from_ref(&self.board))
+            .index_axis_move(Axis(0), 0)
+            .into_pyarray(py)
+    }
+
+    /// Red's pawn, then blue's
+    #[getter]
+    fn pawns(&self) -> [(usize, usize); 2] {
+        self.board.pawns.map(square)
+    }
+
+    /// The player to move: 0 for red, 1 for blue
+    #[getter]
+    fn turn(&self) -> usize {
+        self.board.turn
+    }
+
+    /// The number of plies made since the start of the game
+    #[getter]
+    fn ply(&self) -> usize {
+        self.board.ply()
+    }
+
+    /// The squares the player to move can move to
+    fn legal_moves(&self) -> Vec<(usize, usize)> {
+        self.board.legal_moves().into_iter().map(square).collect()
+    }
+
+    /// Move the player to move to the given square
+    fn make_move(&mut self, row: usize, col: usize) -> PyResult<()> {
+        let point = Point::new(row, col).map_err(PyValueError::new_err)?;
+        if !self.board.legal_moves().contains(&point) {
+            return Err(PyValueError::new_err("Illegal move"));
+        }
+        self.board.make_move(point);
+        Ok(())
+    }
+
+    /// Take back the last move made with `make_move`
+    fn undo_move(&mut self) -> PyResult<()> {
+        if self.board.moves.is_empty() {
+            return Err(PyValueError::new_err("No moves to undo"));
+        }
+        self.board.undo_move();
+        Ok(())
+    }
+
+    /// A perfect-play move (None if the game is over) and its score
+    fn solve(&mut self, py: Python) -> (Option<(usize, usize)>, i8) {
+        let (m, score) = py.detach(|| self.board.best_move_by_cards_remaining());
+        (m.map(square), score)
+    }
+
+    /// The ID of the representative of this starting deal, or None if it is
+    /// not a starting deal
+    fn deal_id(&self) -> Option<u64> {
+        self.board.canonical_deal()?.0.deal_id()
+    }
+}
+
+/// Number of deals up to symmetry, i.e. the number of deal IDs
+#[pyfunction]
+#[pyo3(signature = (old = false))]
+fn deal_count(old: bool) -> u64 {
+    Deals::new(version(old)).len()
+}
+
+/// The deals with IDs from `start` up to (not including) `stop`
+///
+/// Returns the cards as an (n, 4, 4) array, the pawns as an (n, 2, 2) array of
+/// (row, col) for red and blue, and the number of real deals each represents.
+#[pyfunction]
+#[pyo3(signature = (start, stop, old = false))]
+fn deals<'py>(py: Python<'py>, start: u64, stop: u64, old: bool) -> PyResult<Bound<'py, PyDict>> {
+    let (boards, weights) = deal_range(start, stop, old)?;
+    let pawns: Vec<usize> = boards
+        .iter()
+        .flat_map(|board| board.pawns.iter().flat_map(|&Point(x, y)| [x, y]))
+        .collect();
+    let dict = PyDict::new(py);
+    dict.set_item("cards", cards_array(&boards).into_pyarray(py))?;
+    dict.set_item(
+        "pawns",
+        Array3::from_shape_vec((boards.len(), 2, 2), pawns)
+            .expect("Two coordinates per pawn")
+            .into_pyarray(py),
+    )?;
+    dict.set_item("weights", Array1::from(weights).into_pyarray(py))?;
+    Ok(dict)
+}
+
+/// Solve the deals with IDs from `start` up to (not including) `stop`, like
+/// `collapsi solve all` does for every deal
+///
+/// Returns the deal IDs, the number of real deals each represents, and the
+/// score with perfect play, as arrays.
+#[pyfunction]
+#[pyo3(signature = (start, stop, old = false))]
+fn sweep<'py>(py: Python<'py>, start: u64, stop: u64, old: bool) -> PyResult<Bound<'py, PyDict>> {
+    let (boards, weights) = deal_range(start, stop, old)?;
+    let scores: Vec<i8> = py.detach(|| {
+        #[cfg(feature = "parallel")]
+        let boards = boards.into_par_iter();
+        #[cfg(not(feature = "parallel"))]
+        let boards = boards.into_iter();
+        boards
+            .map(|mut board| board.best_move_by_cards_remaining().1)
+            .collect()
+    });
+    let dict = PyDict::new(py);
+    let ids: Vec<u64> = (start..start + weights.len() as u64).collect();
+    dict.set_item("ids", Array1::from(ids).into_pyarray(py))?;
+    dict.set_item("weights", Array1::from(weights).into_pyarray(py))?;
+    dict.set_item("scores", Array1::from(scores).into_pyarray(py))?;
+    Ok(dict)
+}
+
+/// The number of real deals ending with each number of cards remaining, as
+/// reported by `collapsi solve all`, from the results of `sweep`
+#[pyfunction]
+fn score_histogram<'py>(
+    py: Python<'py>,
+    scores: PyReadonlyArray1<i8>,
+    weights: PyReadonlyArray1<u64>,
+) -> PyResult<Bound<'py, PyArray1<u64>>> {
+    let (scores, weights) = (scores.as_array(), weights.as_array());
+    if scores.len() != weights.len() {
+        return Err(PyValueError::new_err(
+            "scores and weights must be the same length",
+        ));
+    }
+    let histogram = histogram(scores.iter().copied().zip(weights.iter().copied()))?;
+    Ok(Array1::from(histogram).into_pyarray(py))
+}
+
+/// The histogram for `score_histogram`, indexed by the number of cards
+/// remaining, or an error if a score is not a possible result
+fn histogram(results: impl Iterator<Item = (i8, u64)>) -> PyResult<Vec<u64>> {
+    let mut histogram = vec![0; SIZE * SIZE];
+    for (score, weight) in results {
+        let Some(count) = histogram.get_mut(score.unsigned_abs() as usize) else {
+            return Err(PyValueError::new_err(format!(
+                "Scores must be between -{0} and {0}, not {1}",
+                SIZE * SIZE - 1,
+                score
+            )));
+        };
+        *count += weight;
+    }
+    Ok(histogram)
+}
+
+/// The deals with IDs in a range, with their weights
+fn deal_range(start: u64, stop: u64, old: bool) -> PyResult<(Vec<Board>, Vec<u64>)> {
+    let deals = Deals::new(version(old));
+    if start > stop || stop > deals.len() {
+        return Err(PyValueError::new_err(format!(
+            "Deal IDs must satisfy start <= stop <= {}",
+            deals.len()
+        )));
+    }
+    Ok((start..stop)
+        .map(|id| deals.get(id).expect("In range"))
+        .unzip())
+}
+
+/// The cards of some boards as an (n, 4, 4) array
+fn cards_array(boards: &[Board]) -> Array3<u8> {
+    let cards: Vec<u8> = boards
+        .iter()
+        .flat_map(|board| board.cards.iter().flatten().copied())
+        .collect();
+    Array3::from_shape_vec((boards.len(), SIZE, SIZE), cards).expect("16 cards per board")
+}
+
+fn square(Point(row, col): Point) -> (usize, usize) {
+    (row, col)
+}
+
+fn version(old: bool) -> CollapsiVersion {
+    if old {
+        CollapsiVersion::V1_1
+    } else {
+        CollapsiVersion::V1_3
+    }
+}
+
+/// The `collapsi` Python module
+#[pymodule]
+fn collapsi(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyBoard>()?;
+    module.add_function(wrap_pyfunction!(deal_count, module)?)?;
+    module.add_function(wrap_pyfunction!(deals, module)?)?;
+    module.add_function(wrap_pyfunction!(sweep, module)?)?;
+    module.add_function(wrap_pyfunction!(score_histogram, module)?)?;
+    Ok(())
+}
+
+// These run Python in this process, so need a Python with numpy installed:
+// `cargo test --features python`
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::py_run;
+
+    /// Run some Python with this module available as `collapsi`
+    fn run(code: &str) {
+        Python::initialize();
+        Python::attach(|py| {
+            let collapsi = PyModule::new(py, "collapsi").unwrap();
+            super::collapsi(&collapsi).unwrap();
+            py_run!(py, collapsi, code);
+        });
+    }
+
+    #[test]
+    fn histogram_rejects_impossible_scores() {
+        let histogram = histogram([(3, 2), (-3, 1), (0, 5), (-15, 1)].into_iter()).unwrap();
+        assert_eq!((histogram[0], histogram[3], histogram[15]), (5, 3, 1));
+        assert!(super::histogram([(16, 1)].into_iter()).is_err());
+        assert!(super::histogram([(i8::MIN, 1)].into_iter()).is_err());
+    }
+
+    #[test]
+    fn board_moves_and_solves() {
+        run(r#"
+            board = collapsi.Board("1223/4121r/3123/1b314/0")
+            assert repr(board) == "Board('1223/4121r/3123/1b314/0')"
+            assert board.pawns == [(1, 3), (3, 0)]
+            assert (board.turn, board.ply) == (0, 0)
+            assert board.solve() == ((1, 0), 3)
+            assert (1, 0) in board.legal_moves()
+            board.make_move(1, 0)
+            assert board.notation() == "1223/4r120/3123/1b314/1"
+            assert board.solve()[1] == 3
+            assert board.deal_id() is None
+            board.undo_move()
+            assert board.ply == 0
+            try:
+                board.make_move(0, 0)
+                raise AssertionError("(0, 0) is not a legal move")
+            except ValueError:
+                pass
+            try:
+                board.undo_move()
+                raise AssertionError("There is no move to undo")
+            except ValueError:
+                pass
+        "#);
+    }
+
+    #[test]
+    fn deals_and_sweep() {
+        run(r#"
+            assert collapsi.deal_count() == 15765750
+            board = collapsi.Board.from_deal_id(12148512)
+            assert board.notation() == "1r343/411b3/1231/2212/0"
+            assert board.deal_id() == 12148512
+            assert board.cards.shape == (4, 4)
+
+            deals = collapsi.deals(0, 3)
+            assert deals["cards"].shape == (3, 4, 4)
+            assert deals["pawns"].shape == (3, 2, 2)
+            result = collapsi.sweep(0, 3)
+            assert list(result["ids"]) == [0, 1, 2]
+            assert list(result["weights"]) == list(deals["weights"])
+            histogram = collapsi.score_histogram(result["scores"], result["weights"])
+            assert histogram.sum() == result["weights"].sum()
+
+            try:
+                collapsi.sweep(3, 2)
+                raise AssertionError("The range is backwards")
+            except ValueError:
+                pass
+            try:
+                collapsi.deals(0, 10**9)
+                raise AssertionError("There are not that many deals")
+            except ValueError:
+                pass
+        "#);
+    }
+
+    #[test]
+    fn score_histogram_checks_its_input() {
+        run(r#"
+            import numpy as np
+            weights = np.array([1, 2], dtype=np.uint64)
+            try:
+                collapsi.score_histogram(np.array([3, 16], dtype=np.int8), weights)
+                raise AssertionError("16 cards cannot remain")
+            except ValueError:
+                pass
+            try:
+                collapsi.score_histogram(np.array([3], dtype=np.int8), weights)
+                raise AssertionError("The lengths differ")
+            except ValueError:
+                pass
+            histogram = collapsi.score_histogram(np.array([3, -3], dtype=np.int8), weights)
+            assert list(histogram[:4]) == [0, 0, 0, 3]
+        "#);
+    }
+}
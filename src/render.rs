@@ -0,0 +1,249 @@
+//! Drawing positions as images: SVG always, and PNG with the `png` feature
+//!
+//! Cards are drawn as in the paper: aces as A, the starting jacks as J (while
+//! a pawn is still on its jack), and flipped cards face down.
+
+use crate::{Board, Point, SIZE};
+use std::fmt::Write;
+
+/// Width of a card, in pixels at scale 1
+const CARD_WIDTH: f64 = 80.0;
+
+/// Height of a card, in pixels at scale 1
+const CARD_HEIGHT: f64 = 90.0;
+
+/// Space between cards, and around the grid for arrows that wrap around
+const GAP: f64 = 12.0;
+const MARGIN: f64 = 30.0;
+
+const RED: &str = "#d62728";
+const BLUE: &str = "#1f5fbf";
+
+/// What to draw besides the cards and pawns
+#[derive(Clone, Debug)]
+pub struct RenderOptions {
+    /// Size of the image relative to the default, which has 80×90 pixel cards
+    pub scale: f64,
+
+    /// Highlight the squares the player to move can move to
+    pub legal_moves: bool,
+
+    /// Squares to join with arrows, for example a move from the pawn's square
+    /// through each card it passes to its destination
+    ///
+    /// Consecutive squares should be neighbours, possibly around the edge of
+    /// the grid; arrows that wrap around leave one side and enter the other.
+    /// They are drawn in the colour of the pawn at either end of the path, or
+    /// of the player to move if there is none.
+    pub path: Vec<Point>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            scale: 1.0,
+            legal_moves: false,
+            path: vec![],
+        }
+    }
+}
+
+impl Board {
+    /// This position as an SVG image
+    pub fn to_svg(&self, options: &RenderOptions) -> String {
+        let size = 2.0 * MARGIN + SIZE as f64 * (CARD_WIDTH + GAP) - GAP;
+        let height = 2.0 * MARGIN + SIZE as f64 * (CARD_HEIGHT + GAP) - GAP;
+        let mover = [RED, BLUE][self.turn];
+        let ends = [options.path.first(), options.path.last()];
+        let arrow = match self
+            .pawns
+            .iter()
+            .position(|pawn| ends.contains(&Some(pawn)))
+        {
+            Some(player) => [RED, BLUE][player],
+            None => mover,
+        };
+        let highlighted = if options.legal_moves {
+            self.legal_moves()
+        } else {
+            Default::default()
+        };
+
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{:.0}" height="{:.0}" viewBox="0 0 {} {}" font-family="DejaVu Sans, Helvetica, Arial, sans-serif">"#,
+            size * options.scale,
+            height * options.scale,
+            size,
+            height
+        )
+        .expect("Writing to a string");
+        writeln!(
+            svg,
+            r##"<defs><pattern id="back" width="8" height="8" patternUnits="userSpaceOnUse" patternTransform="rotate(45)"><rect width="8" height="8" fill="#5b6b8c"/><rect width="4" height="8" fill="#7585a6"/></pattern><marker id="arrow" viewBox="0 0 10 10" refX="8" refY="5" markerWidth="5" markerHeight="5" orient="auto-start-reverse"><path d="M0,0 L10,5 L0,10 z" fill="{}"/></marker></defs>"##,
+            arrow
+        )
+        .expect("Writing to a string");
+        writeln!(
+            svg,
+            r##"<rect width="{}" height="{}" fill="#f4f1ea"/>"##,
+            size, height
+        )
+        .expect("Writing to a string");
+
+        for row in 0..SIZE {
+            for col in 0..SIZE {
+                let point = Point(row, col);
+                self.draw_card(&mut svg, point, highlighted.contains(&point), mover);
+            }
+        }
+        for (player, colour) in [RED, BLUE].iter().enumerate() {
+            draw_pawn(&mut svg, self.pawns[player], colour);
+        }
+        for step in options.path.windows(2) {
+            draw_arrow(&mut svg, step[0], step[1], arrow);
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// This position as a PNG image
+    #[cfg(feature = "png")]
+    pub fn to_png(&self, options: &RenderOptions) -> Result<Vec<u8>, &'static str> {
+        use resvg::{tiny_skia, usvg};
+
+        let mut usvg_options = usvg::Options::default();
+        usvg_options.fontdb_mut().load_system_fonts();
+        let tree = usvg::Tree::from_str(&self.to_svg(options), &usvg_options)
+            .map_err(|_| "Could not read the SVG")?;
+        let size = tree.size().to_int_size();
+        let mut pixmap =
+            tiny_skia::Pixmap::new(size.width(), size.height()).ok_or("The image is empty")?;
+        let transform = tiny_skia::Transform::from_scale(
+            size.width() as f32 / tree.size().width(),
+            size.height() as f32 / tree.size().height(),
+        );
+        resvg::render(&tree, transform, &mut pixmap.as_mut());
+        pixmap.encode_png().map_err(|_| "Could not encode the PNG")
+    }
+
+    /// Draw the card at a point, face up or face down
+    fn draw_card(&self, svg: &mut String, point: Point, highlighted: bool, mover: &str) {
+        let (x, y) = corner(point);
+        let label = self.face(point);
+        let fill = match label {
+            None => "url(#back)",
+            Some(_) if highlighted => "#fff3b0",
+            Some(_) => "white",
+        };
+        let (stroke, stroke_width) = if highlighted { (mover, 4) } else { ("#555", 2) };
+        writeln!(
+            svg,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" rx="8" fill="{}" stroke="{}" stroke-width="{}"/>"#,
+            x, y, CARD_WIDTH, CARD_HEIGHT, fill, stroke, stroke_width
+        )
+        .expect("Writing to a string");
+        let Some(label) = label else { return };
+        if self.pawns.contains(&point) {
+            // Leave room for the pawn
+            writeln!(
+                svg,
+                r##"<text x="{}" y="{}" font-size="20" font-weight="bold" fill="#222">{}</text>"##,
+                x + 8.0,
+                y + 24.0,
+                label
+            )
+            .expect("Writing to a string");
+        } else {
+            writeln!(
+                svg,
+                r##"<text x="{}" y="{}" font-size="40" font-weight="bold" text-anchor="middle" fill="#222">{}</text>"##,
+                x + CARD_WIDTH / 2.0,
+                y + CARD_HEIGHT / 2.0 + 14.0,
+                label
+            )
+            .expect("Writing to a string");
+        }
+    }
+}
+
+/// The top-left corner of the card at a point
+fn corner(Point(row, col): Point) -> (f64, f64) {
+    (
+        MARGIN + col as f64 * (CARD_WIDTH + GAP),
+        MARGIN + row as f64 * (CARD_HEIGHT + GAP),
+    )
+}
+
+/// The centre of the card at a point
+fn centre(point: Point) -> (f64, f64) {
+    let (x, y) = corner(point);
+    (x + CARD_WIDTH / 2.0, y + CARD_HEIGHT / 2.0)
+}
+
+/// Draw a pawn standing on the card at a point
+fn draw_pawn(svg: &mut String, point: Point, colour: &str) {
+    let (x, y) = centre(point);
+    writeln!(
+        svg,
+        r#"<g fill="{colour}" stroke="white" stroke-width="1.5"><path d="M{} {} h32 l-4 -8 h-24 z"/><path d="M{} {} l5 -22 h6 l5 22 z"/><circle cx="{x}" cy="{}" r="9"/></g>"#,
+        x - 16.0,
+        y + 30.0,
+        x - 8.0,
+        y + 22.0,
+        y - 6.0,
+    )
+    .expect("Writing to a string");
+}
+
+/// Draw an arrow from one card to a neighbouring one, leaving one side of the
+/// grid and entering the other if the step wraps around
+fn draw_arrow(svg: &mut String, from: Point, to: Point, colour: &str) {
+    let (x1, y1) = centre(from);
+    let (x2, y2) = centre(to);
+    // The direction of the step, against the difference if it wraps around
+    let mut wraps = false;
+    let mut step = |a: usize, b: usize| -> f64 {
+        let d = b as f64 - a as f64;
+        if d.abs() == (SIZE - 1) as f64 {
+            wraps = true;
+            -d.signum()
+        } else {
+            d
+        }
+    };
+    let (dx, dy) = (step(from.1, to.1), step(from.0, to.0));
+    let mut line = |(x1, y1): (f64, f64), (x2, y2): (f64, f64), head: bool| {
+        writeln!(
+            svg,
+            r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="{}" stroke-width="5" stroke-linecap="round" opacity="0.85"{}/>"#,
+            x1,
+            y1,
+            x2,
+            y2,
+            colour,
+            if head { r#" marker-end="url(#arrow)""# } else { "" }
+        )
+        .expect("Writing to a string");
+    };
+    if wraps {
+        // Off the edge of the grid, then back on at the other side
+        let half = (CARD_WIDTH + GAP) / 2.0 + MARGIN / 2.0;
+        let half_y = (CARD_HEIGHT + GAP) / 2.0 + MARGIN / 2.0;
+        line((x1, y1), (x1 + dx * half, y1 + dy * half_y), false);
+        line(
+            (x2 - dx * half, y2 - dy * half_y),
+            shorten((x2, y2), dx, dy),
+            true,
+        );
+    } else {
+        line((x1, y1), shorten((x2, y2), dx, dy), true);
+    }
+}
+
+/// Stop an arrow short of the centre of a card, so the head stays visible
+/// beside whatever is drawn there
+fn shorten((x, y): (f64, f64), dx: f64, dy: f64) -> (f64, f64) {
+    (x - dx * 18.0, y - dy * 18.0)
+}
@@ -0,0 +1,214 @@
+//! Games exported as HTML slideshows, one slide per ply
+//!
+//! Each slide shows the path the pawn took across the torus, fades from the
+//! position before the move to the position after it (so the card it left is
+//! seen to flip), and gives the evaluation with perfect play from there. The
+//! page is self-contained, with the boards drawn by `to_svg` embedded as
+//! images.
+
+use crate::render::RenderOptions;
+use crate::{Board, Point};
+
+const PLAYERS: [&str; 2] = ["Red", "Blue"];
+
+/// A game to replay: a position and the moves made from it
+#[derive(Clone)]
+pub struct Replay {
+    board: Board,
+    moves: Vec<Point>,
+
+    /// The score with perfect play of each position of the game, from the
+    /// first to the one after the last move
+    scores: Vec<i8>,
+}
+
+impl Replay {
+    /// A replay of the given moves, checking that each is legal
+    ///
+    /// This solves each position of the game, to show its evaluation.
+    pub fn new(board: &Board, moves: Vec<Point>) -> Result<Replay, &'static str> {
+        let mut game = board.clone();
+        let mut scores = vec![];
+        for m in &moves {
+            if !game.legal_moves().contains(m) {
+                return Err("Illegal move in the game");
+            }
+            scores.push(game.best_move_by_cards_remaining().1);
+            game.make_move(*m);
+        }
+        scores.push(game.best_move_by_cards_remaining().1);
+        Ok(Replay {
+            board: board.clone(),
+            moves,
+            scores,
+        })
+    }
+
+    /// A replay of the game from this position with perfect play, as played
+    /// by `simulate` but choosing moves by cards remaining
+    ///
+    /// The search that chooses each move also gives the evaluation of its
+    /// position, so each position is solved only once.
+    pub fn perfect_play(board: &Board) -> Replay {
+        let mut game = board.clone();
+        let mut moves = vec![];
+        let mut scores = vec![];
+        loop {
+            let (m, score) = game.best_move_by_cards_remaining();
+            scores.push(score);
+            let Some(m) = m else {
+                break;
+            };
+            moves.push(m);
+            game.make_move(m);
+        }
+        Replay {
+            board: board.clone(),
+            moves,
+            scores,
+        }
+    }
+
+    /// The replay as a self-contained HTML page
+    pub fn to_html(&self) -> String {
+        let mut board = self.board.clone();
+        let mut slides = vec![slide(
+            &board.to_svg(&RenderOptions::default()),
+            None,
+            &match board.ply() {
+                0 => format!("Start of the game. {}", evaluation(&board, self.scores[0])),
+                ply => format!("After ply {}. {}", ply, evaluation(&board, self.scores[0])),
+            },
+        )];
+        for (m, &score) in self.moves.iter().zip(&self.scores[1..]) {
+            let path = board.move_path(*m).expect("Checked when created").squares;
+            let Point(row, col) = *m;
+            let Point(from_row, from_col) = path[0];
+            let options = RenderOptions {
+                path: path.clone(),
+                ..Default::default()
+            };
+            let before = board.to_svg(&options);
+            let flipped = board.face(path[0]).unwrap_or('?');
+            let mover = PLAYERS[board.turn];
+            board.make_move(*m);
+            let caption = format!(
+                "Ply {}: {} moves from ({},{}) to ({},{}), flipping the {} they left. {}",
+                board.ply(),
+                mover,
+                from_row,
+                from_col,
+                row,
+                col,
+                flipped,
+                evaluation(&board, score)
+            );
+            slides.push(slide(&before, Some(&board.to_svg(&options)), &caption));
+        }
+        format!("{}{}{}", HEADER, slides.join("\n"), FOOTER)
+    }
+}
+
+/// The result of the game with perfect play from this position, given its
+/// score
+fn evaluation(board: &Board, score: i8) -> String {
+    let (winner, cards) = if score > 0 {
+        (PLAYERS[0], score)
+    } else {
+        (PLAYERS[1], -score)
+    };
+    if board.legal_moves().is_empty() {
+        format!(
+            "{} cannot move, so {} wins with {} cards remaining.",
+            PLAYERS[board.turn], winner, cards
+        )
+    } else {
+        format!(
+            "With perfect play, {} wins with {} cards remaining.",
+            winner, cards
+        )
+    }
+}
+
+/// A slide fading from one image to another, if given
+fn slide(before: &str, after: Option<&str>, caption: &str) -> String {
+    let after = after
+        .map(|after| format!("<img class=\"after\" alt=\"\" src=\"{}\">", data_url(after)))
+        .unwrap_or_default();
+    format!(
+        "<section class=\"slide\">\n<div class=\"frames\"><img alt=\"\" src=\"{}\">{}</div>\n<p>{}</p>\n</section>",
+        data_url(before),
+        after,
+        caption
+    )
+}
+
+/// An SVG image as a data URL, escaping the characters that would end it
+fn data_url(svg: &str) -> String {
+    let mut url = "data:image/svg+xml,".to_string();
+    for c in svg.chars() {
+        match c {
+            '%' | '#' | '"' | '<' | '>' | '\n' => url.push_str(&format!("%{:02X}", c as u32)),
+            _ => url.push(c),
+        }
+    }
+    url
+}
+
+const HEADER: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>Collapsi replay</title>
+<style>
+  body { font-family: sans-serif; max-width: 30em; margin: 2em auto; background: #f4f1ea; }
+  .slide { display: none; }
+  .slide.active { display: block; }
+  .frames { position: relative; }
+  .frames img { display: block; width: 100%; height: auto; }
+  .after { position: absolute; top: 0; left: 0; animation: reveal 0.6s ease-in 0.8s both; }
+  @keyframes reveal { from { opacity: 0; } to { opacity: 1; } }
+  nav { display: flex; gap: 0.5em; align-items: center; }
+</style>
+</head>
+<body>
+<nav>
+  <button id="previous">&larr;</button>
+  <button id="play">Play</button>
+  <button id="next">&rarr;</button>
+  <span id="counter"></span>
+</nav>
+"#;
+
+const FOOTER: &str = r#"
+<script>
+  const slides = document.querySelectorAll(".slide");
+  let current = 0;
+  let timer = null;
+  function show(i) {
+    current = Math.max(0, Math.min(slides.length - 1, i));
+    slides.forEach((slide, j) => slide.classList.toggle("active", j === current));
+    document.getElementById("counter").textContent = `${current} / ${slides.length - 1}`;
+  }
+  function stop() {
+    clearInterval(timer);
+    timer = null;
+    document.getElementById("play").textContent = "Play";
+  }
+  document.getElementById("previous").onclick = () => { stop(); show(current - 1); };
+  document.getElementById("next").onclick = () => { stop(); show(current + 1); };
+  document.getElementById("play").onclick = () => {
+    if (timer) return stop();
+    if (current === slides.length - 1) show(0);
+    document.getElementById("play").textContent = "Pause";
+    timer = setInterval(() => current < slides.length - 1 ? show(current + 1) : stop(), 2500);
+  };
+  document.addEventListener("keydown", (event) => {
+    if (event.key === "ArrowLeft") document.getElementById("previous").click();
+    if (event.key === "ArrowRight") document.getElementById("next").click();
+  });
+  show(0);
+</script>
+</body>
+</html>
+"#;
@@ -0,0 +1,130 @@
+//! Estimating statistics over all deals from a random sample, with confidence
+//! intervals
+//!
+//! Samples should come from `Deals::sample`, so that each is as likely as a
+//! random real deal and needs no further weighting.
+
+use std::fmt;
+
+/// Number of standard errors either side of the mean for a 95% confidence
+/// interval, by the normal approximation
+const Z_95: f64 = 1.96;
+
+/// An estimate of the mean of some quantity, from a sample
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Estimate {
+    /// The sample mean
+    pub mean: f64,
+
+    /// Half the width of the 95% confidence interval around the mean
+    pub margin: f64,
+}
+
+impl Estimate {
+    /// Estimate the mean of a quantity from its values in a sample
+    ///
+    /// The confidence interval uses the normal approximation, so it is only
+    /// reliable for large samples, and has zero width if every value is the
+    /// same (for example, if a rare outcome was never seen).
+    pub fn from_samples(values: &[f64]) -> Estimate {
+        if values.is_empty() {
+            return Estimate::default();
+        }
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let margin = if values.len() < 2 {
+            f64::INFINITY
+        } else {
+            let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+            Z_95 * (variance / n).sqrt()
+        };
+        Estimate { mean, margin }
+    }
+
+    /// Estimate the mean of each entry of a histogram, from one histogram per
+    /// sample
+    ///
+    /// Histograms shorter than the longest are treated as having zeroes at
+    /// the end.
+    pub fn from_histograms(histograms: &[Vec<u64>]) -> Vec<Estimate> {
+        let length = histograms.iter().map(Vec::len).max().unwrap_or(0);
+        (0..length)
+            .map(|i| {
+                let values: Vec<f64> = histograms
+                    .iter()
+                    .map(|h| h.get(i).copied().unwrap_or(0) as f64)
+                    .collect();
+                Estimate::from_samples(&values)
+            })
+            .collect()
+    }
+
+    /// The estimate for a multiple of the quantity, for example the total
+    /// over all deals rather than the mean per deal
+    pub fn scale(&self, factor: f64) -> Estimate {
+        Estimate {
+            mean: self.mean * factor,
+            margin: self.margin * factor,
+        }
+    }
+
+    /// The lower and upper ends of the 95% confidence interval
+    pub fn interval(&self) -> (f64, f64) {
+        (self.mean - self.margin, self.mean + self.margin)
+    }
+}
+
+impl fmt::Display for Estimate {
+    /// Written as `mean ± margin`, using the precision given (default 0)
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let precision = f.precision().unwrap_or(0);
+        write!(
+            f,
+            "{:.*} ± {:.*}",
+            precision, self.mean, precision, self.margin
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mean_and_margin_of_a_sample() {
+        let estimate = Estimate::from_samples(&[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(estimate.mean, 2.5);
+        // The sample variance is (2.25 + 0.25 + 0.25 + 2.25) / 3 = 5/3, so
+        // the standard error is sqrt(5/3 / 4)
+        assert!((estimate.margin - 1.96 * (5.0f64 / 12.0).sqrt()).abs() < 1e-12);
+        let (low, high) = estimate.interval();
+        assert!((low + high - 5.0).abs() < 1e-12);
+
+        let constant = Estimate::from_samples(&[3.0, 3.0, 3.0]);
+        assert_eq!(
+            constant,
+            Estimate {
+                mean: 3.0,
+                margin: 0.0
+            }
+        );
+    }
+
+    #[test]
+    fn tiny_samples() {
+        assert_eq!(Estimate::from_samples(&[]), Estimate::default());
+        let single = Estimate::from_samples(&[7.0]);
+        assert_eq!(single.mean, 7.0);
+        assert_eq!(single.margin, f64::INFINITY);
+    }
+
+    #[test]
+    fn short_histograms_are_padded_with_zeroes() {
+        let estimates = Estimate::from_histograms(&[vec![1, 2], vec![3], vec![5, 0, 4]]);
+        let means: Vec<f64> = estimates.iter().map(|e| e.mean).collect();
+        assert_eq!(means, [3.0, 2.0 / 3.0, 4.0 / 3.0]);
+        assert_eq!(estimates[0], Estimate::from_samples(&[1.0, 3.0, 5.0]));
+        assert_eq!(estimates[2], Estimate::from_samples(&[0.0, 0.0, 4.0]));
+        assert!(Estimate::from_histograms(&[]).is_empty());
+    }
+}
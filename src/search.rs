@@ -0,0 +1,540 @@
+//! Bounded versions of the exact search functions, which can be limited by
+//! node count or time, or cancelled from another thread
+
+use crate::{Board, Instant, Point, SIZE};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// How often (in nodes) to check the clock and the stop flag
+const CHECK_INTERVAL: u64 = 1024;
+
+/// Limits on how much work a search may do before giving up
+///
+/// The default has no limits, and searches to the end of the game.
+#[derive(Clone, Debug, Default)]
+pub struct SearchOptions {
+    /// Give up after visiting this many positions
+    pub max_nodes: Option<u64>,
+
+    /// Give up after this moment in time
+    pub deadline: Option<Instant>,
+
+    /// Give up as soon as this is set to true, for example by another thread
+    pub stop: Option<Arc<AtomicBool>>,
+
+    /// Whether to collect `SearchStats`, which costs a little time
+    pub collect_stats: bool,
+
+    /// The order in which alpha-beta search tries the moves in each position
+    pub ordering: MoveOrdering,
+
+    /// Most positions remembered by the `TranspositionTable` ordering, or None
+    /// for no limit
+    pub table_size: Option<usize>,
+}
+
+/// Heuristics for choosing which move alpha-beta search tries first
+///
+/// A good ordering tries the best move first, so that the remaining moves are
+/// more likely to be cut off. Every ordering is deterministic.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MoveOrdering {
+    /// Moves in order of their coordinates
+    #[default]
+    Natural,
+
+    /// Moves that leave the opponent with the fewest replies first
+    OpponentMobility,
+
+    /// Moves that recently caused a cutoff at the same depth first
+    Killer,
+
+    /// Moves that have caused the most cutoffs over the whole search first,
+    /// weighted towards cutoffs near the root
+    History,
+
+    /// The best move found last time this position was searched first, if it
+    /// was reached by a different sequence of moves
+    TranspositionTable,
+}
+
+impl MoveOrdering {
+    /// Every available ordering, for comparing them
+    pub const ALL: [MoveOrdering; 5] = [
+        MoveOrdering::Natural,
+        MoveOrdering::OpponentMobility,
+        MoveOrdering::Killer,
+        MoveOrdering::History,
+        MoveOrdering::TranspositionTable,
+    ];
+}
+
+impl FromStr for MoveOrdering {
+    type Err = &'static str;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "natural" => Ok(MoveOrdering::Natural),
+            "mobility" => Ok(MoveOrdering::OpponentMobility),
+            "killer" => Ok(MoveOrdering::Killer),
+            "history" => Ok(MoveOrdering::History),
+            "tt" => Ok(MoveOrdering::TranspositionTable),
+            _ => Err("Unknown move ordering"),
+        }
+    }
+}
+
+/// The outcome of a search that may not have finished
+#[derive(Clone, Debug)]
+pub struct SearchResult<T> {
+    /// The answer, or the best answer known when the search was stopped
+    pub value: T,
+
+    /// Whether the search finished, so that `value` is exact
+    pub completed: bool,
+
+    /// Number of positions visited
+    pub nodes: u64,
+
+    /// Details of the search, if requested with `collect_stats`
+    pub stats: Option<SearchStats>,
+}
+
+/// Measurements of the work done by one or more searches
+#[derive(Clone, Debug, Default)]
+pub struct SearchStats {
+    /// Number of positions visited
+    pub nodes: u64,
+
+    /// Number of searches these statistics were collected from, each of
+    /// which visited one root position
+    pub roots: u64,
+
+    /// Number of visited positions in which the game was over
+    pub leaves: u64,
+
+    /// Number of alpha-beta cutoffs, indexed by depth below the root
+    pub cutoffs_by_depth: Vec<u64>,
+
+    /// Deepest position visited, in plies below the root
+    pub max_depth: usize,
+
+    /// Time spent searching (summed over threads when merged)
+    pub elapsed: Duration,
+}
+
+impl SearchStats {
+    /// Total alpha-beta cutoffs at all depths
+    pub fn cutoffs(&self) -> u64 {
+        self.cutoffs_by_depth.iter().sum()
+    }
+
+    /// Average number of moves searched from each position that was not a
+    /// leaf, which alpha-beta cutoffs reduce
+    pub fn effective_branching_factor(&self) -> f64 {
+        let interior = self.nodes - self.leaves;
+        if interior == 0 {
+            0.0
+        } else {
+            // Every visited position except the roots was reached by a move
+            (self.nodes - self.roots) as f64 / interior as f64
+        }
+    }
+
+    /// Combine with the statistics of another search, for example one running
+    /// on another board in parallel
+    pub fn merge(mut self, other: SearchStats) -> SearchStats {
+        self.nodes += other.nodes;
+        self.roots += other.roots;
+        self.leaves += other.leaves;
+        if self.cutoffs_by_depth.len() < other.cutoffs_by_depth.len() {
+            self.cutoffs_by_depth
+                .resize(other.cutoffs_by_depth.len(), 0);
+        }
+        for (total, cutoffs) in self.cutoffs_by_depth.iter_mut().zip(other.cutoffs_by_depth) {
+            *total += cutoffs;
+        }
+        self.max_depth = self.max_depth.max(other.max_depth);
+        self.elapsed += other.elapsed;
+        self
+    }
+}
+
+impl fmt::Display for SearchStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Nodes: {}", self.nodes)?;
+        writeln!(f, "Leaves: {}", self.leaves)?;
+        writeln!(
+            f,
+            "Cutoffs: {} by depth {:?}",
+            self.cutoffs(),
+            self.cutoffs_by_depth
+        )?;
+        writeln!(f, "Max depth: {}", self.max_depth)?;
+        writeln!(
+            f,
+            "Effective branching factor: {:.3}",
+            self.effective_branching_factor()
+        )?;
+        write!(f, "Time: {:.3?}", self.elapsed)
+    }
+}
+
+/// The state of a search in progress, passed through the recursive search
+/// functions
+pub(crate) struct Search {
+    options: SearchOptions,
+    nodes: u64,
+    aborted: bool,
+
+    /// The ply (number of moves made) at the root of the search
+    root_ply: Option<usize>,
+
+    started: Instant,
+    stats: Option<SearchStats>,
+
+    /// Up to two moves that caused cutoffs at each ply, most recent first
+    killers: [[Option<Point>; 2]; SIZE * SIZE + 1],
+
+    /// Cutoff scores for each (from, to) pair of points
+    history: HashMap<(Point, Point), u64>,
+
+    /// Best move found in each position, by `Board::key`
+    best_moves: HashMap<u64, Point>,
+}
+
+impl Search {
+    pub(crate) fn new(options: SearchOptions) -> Search {
+        let stats = options.collect_stats.then(|| SearchStats {
+            roots: 1,
+            ..Default::default()
+        });
+        Search {
+            options,
+            nodes: 0,
+            aborted: false,
+            root_ply: None,
+            started: Instant::now(),
+            stats,
+            killers: [[None; 2]; SIZE * SIZE + 1],
+            history: HashMap::new(),
+            best_moves: HashMap::new(),
+        }
+    }
+
+    /// A search with no limits
+    pub(crate) fn unlimited() -> Search {
+        Search::new(SearchOptions::default())
+    }
+
+    /// Record a visit to a new position at the given ply, returning true if
+    /// the search should stop
+    pub(crate) fn visit(&mut self, ply: usize) -> bool {
+        if self.aborted {
+            return true;
+        }
+        self.nodes += 1;
+        if let Some(stats) = &mut self.stats {
+            let root_ply = *self.root_ply.get_or_insert(ply);
+            stats.nodes += 1;
+            stats.max_depth = stats.max_depth.max(ply - root_ply);
+        }
+        if self.options.max_nodes.is_some_and(|max| self.nodes > max) {
+            self.aborted = true;
+        } else if self.nodes.is_multiple_of(CHECK_INTERVAL) {
+            let timed_out = self
+                .options
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline);
+            let stopped = self
+                .options
+                .stop
+                .as_ref()
+                .is_some_and(|stop| stop.load(Ordering::Relaxed));
+            self.aborted = timed_out || stopped;
+        }
+        self.aborted
+    }
+
+    /// Record that the game is over in the position just visited
+    pub(crate) fn leaf(&mut self) {
+        if let Some(stats) = &mut self.stats {
+            stats.leaves += 1;
+        }
+    }
+
+    /// Record an alpha-beta cutoff in a position at the given ply
+    pub(crate) fn cutoff(&mut self, ply: usize) {
+        if let (Some(stats), Some(root_ply)) = (&mut self.stats, self.root_ply) {
+            let depth = ply - root_ply;
+            if stats.cutoffs_by_depth.len() <= depth {
+                stats.cutoffs_by_depth.resize(depth + 1, 0);
+            }
+            stats.cutoffs_by_depth[depth] += 1;
+        }
+    }
+
+    /// Put the legal moves of `board` in the order they should be searched
+    pub(crate) fn order_moves(
+        &self,
+        board: &mut Board,
+        moves: impl IntoIterator<Item = Point>,
+    ) -> Vec<Point> {
+        let mut moves: Vec<Point> = moves.into_iter().collect();
+        let ply = board.moves.len();
+        let from = board.pawns[board.turn];
+        match self.options.ordering {
+            MoveOrdering::Natural => (),
+            MoveOrdering::OpponentMobility => moves.sort_by_cached_key(|m| {
+                board.make_move(*m);
+                let replies = board.legal_moves().len();
+                board.undo_move();
+                replies
+            }),
+            MoveOrdering::Killer => {
+                moves.sort_by_key(|m| !self.killers[ply].contains(&Some(*m)));
+            }
+            MoveOrdering::History => moves.sort_by_key(|m| {
+                std::cmp::Reverse(self.history.get(&(from, *m)).copied().unwrap_or(0))
+            }),
+            MoveOrdering::TranspositionTable => {
+                if let Some(best) = self.best_moves.get(&board.key()) {
+                    moves.sort_by_key(|m| m != best);
+                }
+            }
+        }
+        moves
+    }
+
+    /// Remember the best move found in a fully-searched position, and whether
+    /// it caused a cutoff, for use by the move ordering
+    pub(crate) fn record_best_move(&mut self, board: &Board, best: Point, cutoff: bool) {
+        let ply = board.moves.len();
+        match self.options.ordering {
+            MoveOrdering::Killer if cutoff && self.killers[ply][0] != Some(best) => {
+                self.killers[ply] = [Some(best), self.killers[ply][0]];
+            }
+            MoveOrdering::History if cutoff => {
+                let remaining = (SIZE * SIZE - ply) as u64;
+                *self
+                    .history
+                    .entry((board.pawns[board.turn], best))
+                    .or_default() += remaining * remaining;
+            }
+            MoveOrdering::TranspositionTable => {
+                let key = board.key();
+                let full = self
+                    .options
+                    .table_size
+                    .is_some_and(|size| self.best_moves.len() >= size);
+                if !full || self.best_moves.contains_key(&key) {
+                    self.best_moves.insert(key, best);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Whether the search has been stopped early
+    pub(crate) fn aborted(&self) -> bool {
+        self.aborted
+    }
+
+    /// Package a value with the state of this search
+    pub(crate) fn result<T>(&self, value: T) -> SearchResult<T> {
+        SearchResult {
+            value,
+            completed: !self.aborted,
+            nodes: self.nodes,
+            stats: self.stats.clone().map(|stats| SearchStats {
+                elapsed: self.started.elapsed(),
+                ..stats
+            }),
+        }
+    }
+}
+
+impl Board {
+    /// Like `winning_move`, but within the given limits
+    ///
+    /// If the search does not complete, the value is None unless a winning
+    /// move was already proven.
+    pub fn winning_move_limited(&mut self, options: SearchOptions) -> SearchResult<Option<Point>> {
+        let mut search = Search::new(options);
+        let m = self.winning_move_searching(&mut search);
+        search.result(m)
+    }
+
+    /// Like `best_move_by_cards_remaining`, but within the given limits
+    ///
+    /// If the search does not complete, the value is the best of the moves
+    /// that were fully searched, with the score it was shown to achieve so
+    /// far. If no move was fully searched, the move is None and the score is
+    /// the worst case for the player to move, ±16, which says nothing about
+    /// the position.
+    pub fn best_move_by_cards_remaining_limited(
+        &mut self,
+        options: SearchOptions,
+    ) -> SearchResult<(Option<Point>, i8)> {
+        let mut search = Search::new(options);
+        let best = self.best_move_by_cards_remaining_bounded(-16, 16, &mut search);
+        search.result(best)
+    }
+
+    /// Like `number_of_possible_games`, but within the given limits
+    ///
+    /// If the search does not complete, the value is the number of games
+    /// counted so far, which is a lower bound.
+    pub fn number_of_possible_games_limited(
+        &mut self,
+        options: SearchOptions,
+    ) -> SearchResult<u64> {
+        let mut search = Search::new(options);
+        let games = *self.count_game_tree_leaves(&mut 0, &mut search);
+        search.result(games)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_limit_stops_search() {
+        let result = Board::example().best_move_by_cards_remaining_limited(SearchOptions {
+            max_nodes: Some(1),
+            ..Default::default()
+        });
+        assert!(!result.completed);
+        assert_eq!(result.value.0, None);
+    }
+
+    #[test]
+    fn stop_flag_stops_search() {
+        let result = Board::example().best_move_by_cards_remaining_limited(SearchOptions {
+            stop: Some(Arc::new(AtomicBool::new(true))),
+            ..Default::default()
+        });
+        assert!(!result.completed);
+        assert_eq!(result.nodes, CHECK_INTERVAL);
+        assert_eq!(result.value.0, None);
+    }
+
+    #[test]
+    fn node_limit_stops_winning_move() {
+        let mut board = Board::example();
+        let full = board.winning_move_limited(SearchOptions::default());
+        assert!(full.completed);
+        assert_eq!(full.value, board.winning_move());
+        assert!(full.value.is_some());
+
+        let result = board.winning_move_limited(SearchOptions {
+            max_nodes: Some(1),
+            ..Default::default()
+        });
+        assert!(!result.completed);
+        assert_eq!(result.value, None);
+    }
+
+    #[test]
+    fn stop_flag_stops_winning_move() {
+        let result = Board::example().winning_move_limited(SearchOptions {
+            stop: Some(Arc::new(AtomicBool::new(true))),
+            ..Default::default()
+        });
+        assert!(!result.completed);
+        assert_eq!(result.nodes, CHECK_INTERVAL);
+        assert_eq!(result.value, None);
+    }
+
+    #[test]
+    fn passed_deadline_stops_search() {
+        let result = Board::example().number_of_possible_games_limited(SearchOptions {
+            deadline: Some(Instant::now()),
+            ..Default::default()
+        });
+        assert!(!result.completed);
+    }
+
+    #[test]
+    fn partial_search_keeps_completed_moves() {
+        let mut board = Board::example();
+        let (_, exact) = board.best_move_by_cards_remaining();
+        let full = board.best_move_by_cards_remaining_limited(SearchOptions::default());
+        assert!(full.completed);
+        assert_eq!(full.value.1, exact);
+
+        let partial = board.best_move_by_cards_remaining_limited(SearchOptions {
+            max_nodes: Some(full.nodes - 1),
+            ..Default::default()
+        });
+        assert!(!partial.completed);
+        assert!(partial.value.0.is_some());
+    }
+
+    #[test]
+    fn branching_factor_of_one_search() {
+        let mut board = Board::example();
+        let stats = board
+            .best_move_by_cards_remaining_limited(SearchOptions {
+                collect_stats: true,
+                ..Default::default()
+            })
+            .stats
+            .unwrap();
+        assert_eq!(stats.roots, 1);
+        let interior = stats.nodes - stats.leaves;
+        assert_eq!(
+            stats.effective_branching_factor(),
+            (stats.nodes - 1) as f64 / interior as f64
+        );
+    }
+
+    #[test]
+    fn branching_factor_of_merged_searches() {
+        // A root with two moves, each ending the game, repeated three times
+        let one = SearchStats {
+            nodes: 3,
+            roots: 1,
+            leaves: 2,
+            ..Default::default()
+        };
+        assert_eq!(one.effective_branching_factor(), 2.0);
+        let merged = SearchStats::default()
+            .merge(one.clone())
+            .merge(one.clone())
+            .merge(one);
+        assert_eq!(merged.roots, 3);
+        assert_eq!(merged.nodes, 9);
+        assert_eq!(merged.effective_branching_factor(), 2.0);
+    }
+
+    #[test]
+    fn branching_factor_without_interior_nodes() {
+        assert_eq!(SearchStats::default().effective_branching_factor(), 0.0);
+    }
+
+    #[test]
+    fn every_ordering_finds_the_same_score() {
+        let mut board = Board::example();
+        for m in board.perfect_game().into_iter().take(3) {
+            let natural = board.best_move_by_cards_remaining_limited(SearchOptions::default());
+            for ordering in MoveOrdering::ALL {
+                for table_size in [None, Some(16)] {
+                    let result = board.best_move_by_cards_remaining_limited(SearchOptions {
+                        ordering,
+                        table_size,
+                        ..Default::default()
+                    });
+                    assert!(result.completed);
+                    assert_eq!(result.value.1, natural.value.1, "{:?}", ordering);
+                }
+            }
+            board.make_move(m);
+        }
+    }
+}
@@ -0,0 +1,344 @@
+//! A small HTTP server answering solver queries with JSON
+//!
+//! Endpoints (boards are given in the notation read by `Board::new`):
+//!
+//! - `POST /solve` with `{"board": "..."}`: a perfect-play move and its score
+//! - `POST /analyse` with `{"board": "..."}`: the score of every legal move,
+//!   each searched for an equal share of the time
+//! - `POST /legal-moves` with `{"board": "..."}`: the legal moves, with the
+//!   board after each and every path to it (the squares visited, and whether
+//!   each step wraps around an edge)
+//! - `GET /deal/{id}` (or `/deal/{id}?old` for the old rules): the starting
+//!   deal with a given ID
+//!
+//! Moves are written `{"row": r, "col": c}`, and scores are from red's point of
+//! view, as in `best_move_by_cards_remaining`. Searches that run out of time
+//! report the best answer found so far with `"completed": false`. Errors are
+//! reported as `{"error": "..."}` with a 4xx or 5xx status.
+
+use crate::search::SearchOptions;
+use crate::{Board, CollapsiVersion, Instant, MovePath, Point};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// Largest request body accepted, in bytes
+const MAX_BODY: usize = 64 * 1024;
+
+/// Settings for the server
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    /// Address to listen on, for example "127.0.0.1:8080" (port 0 picks any
+    /// free port)
+    pub address: String,
+
+    /// Longest time to spend searching for one request; also the longest time
+    /// to wait for a client to send its request
+    pub timeout: Duration,
+
+    /// Most requests handled at once; more are turned away with status 503
+    pub max_concurrent: usize,
+
+    /// Most responses remembered, to answer repeated requests instantly
+    pub cache_size: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            address: "127.0.0.1:8080".to_string(),
+            timeout: Duration::from_secs(10),
+            max_concurrent: 4,
+            cache_size: 10_000,
+        }
+    }
+}
+
+/// The body of the POST requests
+#[derive(Deserialize)]
+struct BoardRequest {
+    board: String,
+}
+
+/// A response: an HTTP status code and a JSON body
+type Response = (u16, Value);
+
+/// Responses to completed requests, forgetting the oldest when full
+#[derive(Default)]
+struct Cache {
+    responses: HashMap<String, Response>,
+    order: VecDeque<String>,
+
+    /// Number of requests answered from the cache
+    hits: u64,
+}
+
+/// A server bound to its address, ready to run
+pub struct Server {
+    listener: TcpListener,
+    config: ServerConfig,
+    cache: Mutex<Cache>,
+    active: AtomicUsize,
+}
+
+impl Server {
+    /// Start listening on `config.address`
+    pub fn bind(config: ServerConfig) -> io::Result<Server> {
+        Ok(Server {
+            listener: TcpListener::bind(&config.address)?,
+            config,
+            cache: Mutex::new(Cache::default()),
+            active: AtomicUsize::new(0),
+        })
+    }
+
+    /// The address actually listened on
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Number of responses in the cache
+    pub fn cache_len(&self) -> usize {
+        self.cache.lock().expect("Not poisoned").responses.len()
+    }
+
+    /// Number of requests answered from the cache so far
+    pub fn cache_hits(&self) -> u64 {
+        self.cache.lock().expect("Not poisoned").hits
+    }
+
+    /// Answer requests forever, each on its own thread
+    pub fn run(self) {
+        let server = Arc::new(self);
+        for stream in server.listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            let server = Arc::clone(&server);
+            thread::spawn(move || server.handle_connection(stream));
+        }
+    }
+
+    /// Read one request from a client and send the response
+    ///
+    /// Each connection carries a single request, and is closed afterwards.
+    fn handle_connection(&self, mut stream: TcpStream) {
+        // Connections count towards the limit while their request is read, so
+        // a slow client takes up a slot until it finishes sending or times
+        // out. Its thread is only released then, even if it is turned away.
+        let busy = self.active.fetch_add(1, Ordering::SeqCst) >= self.config.max_concurrent;
+        let request = stream
+            .set_read_timeout(Some(self.config.timeout))
+            .and_then(|_| read_request(&stream));
+        let response = match request {
+            Err(_) => error(400, "Could not read the request"),
+            Ok(_) if busy => error(503, "Too many requests at once; try again later"),
+            Ok((method, path, body)) => self.respond(&method, &path, &body),
+        };
+        self.active.fetch_sub(1, Ordering::SeqCst);
+        let (status, body) = response;
+        let body = body.to_string();
+        let _ = write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            reason(status),
+            body.len(),
+            body
+        );
+    }
+
+    /// The response to a request, from the cache if possible
+    ///
+    /// This is independent of the network, for testing.
+    pub fn respond(&self, method: &str, path: &str, body: &str) -> (u16, Value) {
+        let key = format!("{} {} {}", method, path, body);
+        {
+            let mut cache = self.cache.lock().expect("Not poisoned");
+            if let Some(response) = cache.responses.get(&key).cloned() {
+                cache.hits += 1;
+                return response;
+            }
+        }
+        let response = self.route(method, path, body);
+        let completed = response.1.get("completed") != Some(&Value::Bool(false));
+        if response.0 == 200 && completed && self.config.cache_size > 0 {
+            let mut cache = self.cache.lock().expect("Not poisoned");
+            if cache.order.len() >= self.config.cache_size
+                && let Some(oldest) = cache.order.pop_front()
+            {
+                cache.responses.remove(&oldest);
+            }
+            cache.order.push_back(key.clone());
+            cache.responses.insert(key, response.clone());
+        }
+        response
+    }
+
+    /// The response to a request, worked out from scratch
+    fn route(&self, method: &str, path: &str, body: &str) -> Response {
+        let options = SearchOptions {
+            deadline: Some(Instant::now() + self.config.timeout),
+            ..Default::default()
+        };
+        match (method, path) {
+            ("POST", "/solve") => with_board(body, |board| solve(board, options)),
+            ("POST", "/analyse") => with_board(body, |board| analyse(board, options)),
+            ("POST", "/legal-moves") => with_board(body, legal_moves),
+            ("GET", _) if path.starts_with("/deal/") => deal(&path["/deal/".len()..]),
+            _ => error(404, "Unknown endpoint"),
+        }
+    }
+}
+
+/// Read the method, path and body of an HTTP request
+fn read_request(stream: &TcpStream) -> io::Result<(String, String, String)> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut length = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            length = value.trim().parse().unwrap_or(0);
+        }
+    }
+    if length > MAX_BODY {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Body too large"));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok((method, path, String::from_utf8_lossy(&body).into_owned()))
+}
+
+/// Parse the board in a request body and pass it on
+fn with_board(body: &str, f: impl FnOnce(&mut Board) -> Response) -> Response {
+    let request: BoardRequest = match serde_json::from_str(body) {
+        Ok(request) => request,
+        Err(_) => return error(400, r#"Expected a JSON body like {"board": "..."}"#),
+    };
+    match Board::new(&request.board) {
+        Ok(mut board) => f(&mut board),
+        Err(message) => error(400, &format!("Invalid board: {}", message)),
+    }
+}
+
+fn solve(board: &mut Board, options: SearchOptions) -> Response {
+    // Starting deals are solved via their representative, as in the CLI
+    let result = match board.best_move_via_representative_limited(options.clone()) {
+        Some(result) => result,
+        None => board.best_move_by_cards_remaining_limited(options),
+    };
+    let (m, score) = result.value;
+    // The score means nothing if the search stopped before finishing any move
+    let score = (m.is_some() || result.completed).then_some(score);
+    (
+        200,
+        json!({
+            "board": board.notation(),
+            "best_move": m.map(square),
+            "score": score,
+            "completed": result.completed,
+            "nodes": result.nodes,
+        }),
+    )
+}
+
+fn analyse(board: &mut Board, options: SearchOptions) -> Response {
+    let legal = board.legal_moves();
+    let mut completed = true;
+    let mut moves = vec![];
+    for (i, &m) in legal.iter().enumerate() {
+        // Each move gets an equal share of the time left, so that one slow
+        // move does not leave the others no time at all
+        let deadline = options.deadline.map(|deadline| {
+            let now = Instant::now();
+            now + deadline.saturating_duration_since(now) / (legal.len() - i) as u32
+        });
+        board.make_move(m);
+        let result = board.best_move_by_cards_remaining_limited(SearchOptions {
+            deadline,
+            ..options.clone()
+        });
+        board.undo_move();
+        completed &= result.completed;
+        // As for /solve, the score means nothing if no reply was finished
+        let (reply, score) = result.value;
+        let score = (reply.is_some() || result.completed).then_some(score);
+        moves.push(json!({
+            "move": square(m),
+            "score": score,
+            "completed": result.completed,
+        }));
+    }
+    (
+        200,
+        json!({"board": board.notation(), "moves": moves, "completed": completed}),
+    )
+}
+
+fn legal_moves(board: &mut Board) -> Response {
+    let moves: Vec<Value> = board
+        .move_paths()
+        .into_iter()
+        .map(|(m, paths)| {
+            board.make_move(m);
+            let after = board.notation();
+            board.undo_move();
+            let paths: Vec<Value> = paths.iter().map(path).collect();
+            json!({"move": square(m), "board": after, "paths": paths})
+        })
+        .collect();
+    (200, json!({"board": board.notation(), "moves": moves}))
+}
+
+fn deal(id: &str) -> Response {
+    let (id, version) = match id.strip_suffix("?old") {
+        Some(id) => (id, CollapsiVersion::V1_1),
+        None => (id, CollapsiVersion::V1_3),
+    };
+    let Ok(id) = id.parse() else {
+        return error(400, "Deal IDs must be numbers");
+    };
+    match Board::from_deal_id(version, id) {
+        Ok(board) => (200, json!({"id": id, "board": board.notation()})),
+        Err(message) => error(404, message),
+    }
+}
+
+fn square(Point(row, col): Point) -> Value {
+    json!({"row": row, "col": col})
+}
+
+fn path(path: &MovePath) -> Value {
+    let squares: Vec<Value> = path.squares.iter().map(|s| square(*s)).collect();
+    json!({"squares": squares, "wraps": path.wraps})
+}
+
+fn error(status: u16, message: &str) -> Response {
+    (status, json!({"error": message}))
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        503 => "Service Unavailable",
+        _ => "Error",
+    }
+}
@@ -0,0 +1,350 @@
+//! Symmetries of the toroidal board, used to match any deal with its
+//! representative in `Deals`
+//!
+//! Card suits and colours play no part in the game, so deals are already
+//! identified by card values alone; the remaining symmetries are the toroidal
+//! shifts, rotations and reflections (including in the diagonals).
+
+use crate::deals::{BLUE_JACKS, Deals, LAYOUTS};
+use crate::search::{SearchOptions, SearchResult};
+use crate::{Board, CollapsiVersion, Point, SIZE};
+use std::fmt;
+
+/// A symmetry of the board: a toroidal shift followed by a rotation or
+/// reflection about the top-left corner
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Symmetry {
+    /// The point that is shifted to the top-left corner
+    shift: Point,
+
+    /// Which of the 8 rotations and reflections to apply after shifting
+    ///
+    /// Bit 0 negates the row, bit 1 negates the column, and bit 2 swaps the
+    /// row and column (reflecting in the main diagonal).
+    dihedral: u8,
+}
+
+impl Symmetry {
+    /// Every symmetry of the board
+    pub fn all() -> impl Iterator<Item = Symmetry> {
+        (0..SIZE * SIZE).flat_map(|i| {
+            (0..8).map(move |dihedral| Symmetry {
+                shift: Point(i / SIZE, i % SIZE),
+                dihedral,
+            })
+        })
+    }
+
+    /// Where this symmetry sends a point
+    pub fn apply(&self, point: Point) -> Point {
+        let Point(x, y) = point + Point(SIZE - self.shift.0, SIZE - self.shift.1);
+        let negate = |v: usize, bit: u8| {
+            if self.dihedral & bit != 0 {
+                (SIZE - v) % SIZE
+            } else {
+                v
+            }
+        };
+        let (x, y) = (negate(x, 1), negate(y, 2));
+        if self.dihedral & 4 != 0 {
+            Point(y, x)
+        } else {
+            Point(x, y)
+        }
+    }
+
+    /// The point this symmetry sends to `point`, undoing `apply`
+    pub fn invert(&self, point: Point) -> Point {
+        (0..SIZE * SIZE)
+            .map(|i| Point(i / SIZE, i % SIZE))
+            .find(|p| self.apply(*p) == point)
+            .expect("Symmetries are bijections")
+    }
+
+    /// The transformed copy of a board
+    pub fn apply_to_board(&self, board: &Board) -> Board {
+        let mut out = board.clone();
+        for x in 0..SIZE {
+            for y in 0..SIZE {
+                out.set_card(self.apply(Point(x, y)), board.cards[x][y]);
+            }
+        }
+        out.pawns = board.pawns.map(|p| self.apply(p));
+        out.moves = board
+            .moves
+            .iter()
+            .map(|(dist, from)| (*dist, self.apply(*from)))
+            .collect();
+        out
+    }
+}
+
+/// The outcome of checking that `Deals` and its weights account for every
+/// possible deal
+///
+/// Each deal should be mapped onto the representatives by exactly 8 / w
+/// symmetries, where w is the weight of the representatives it reaches.
+/// Conversely, each representative should be reached by exactly 8 (deal,
+/// symmetry) pairs, so that it stands for w deals in total.
+///
+/// The game is unchanged by toroidal shifts, so every deal is a shift of one
+/// with the red jack at the top-left. The full check therefore enumerates only
+/// those 15 × 14!/(4!4!4!2!) deals (`Deals::get_unreduced`), not all
+/// 16!/(4!4!4!2!2!) of them, and only the rotations and reflections about the
+/// top-left are tried on each.
+#[derive(Clone, Debug, Default)]
+pub struct WeightCheck {
+    /// Number of deals checked
+    pub deals: u64,
+
+    /// Deals that no symmetry maps onto a representative
+    pub unmatched: u64,
+
+    /// Deals reached by a number of symmetries that does not match the
+    /// weight of their representatives
+    pub wrong_weight: u64,
+
+    /// Deals whose score was compared with their representative's
+    pub solved: u64,
+
+    /// Solved deals whose score differs from their representative's, or where
+    /// the representative's best move, translated back, does not achieve it
+    pub wrong_score: u64,
+}
+
+impl WeightCheck {
+    /// Whether no problems were found
+    pub fn passed(&self) -> bool {
+        self.unmatched == 0 && self.wrong_weight == 0 && self.wrong_score == 0
+    }
+
+    /// Combine with the results of checking other deals
+    pub fn merge(self, other: WeightCheck) -> WeightCheck {
+        WeightCheck {
+            deals: self.deals + other.deals,
+            unmatched: self.unmatched + other.unmatched,
+            wrong_weight: self.wrong_weight + other.wrong_weight,
+            solved: self.solved + other.solved,
+            wrong_score: self.wrong_score + other.wrong_score,
+        }
+    }
+}
+
+impl fmt::Display for WeightCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} deals checked", self.deals)?;
+        writeln!(f, "{} deals match no representative", self.unmatched)?;
+        writeln!(
+            f,
+            "{} deals match representatives with the wrong weight",
+            self.wrong_weight
+        )?;
+        write!(
+            f,
+            "{} of {} solved deals score differently from their representative",
+            self.wrong_score, self.solved
+        )
+    }
+}
+
+impl fmt::Display for Symmetry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Point(x, y) = self.shift;
+        write!(f, "shift ({}, {}) to the top-left", x, y)?;
+        for (bit, name) in [
+            (1, "flip rows"),
+            (2, "flip columns"),
+            (4, "swap rows and columns"),
+        ] {
+            if self.dihedral & bit != 0 {
+                write!(f, ", {}", name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Board {
+    /// The version of the rules this starting deal was dealt for, or None if
+    /// it is not a legal starting deal
+    ///
+    /// A legal deal has four cards of each of 1, 2 and 3, two 4s, and two
+    /// jacks with the pawns on them. The jacks count as 0 in v1.1 and 1 in
+    /// v1.3.
+    pub fn deal_version(&self) -> Option<CollapsiVersion> {
+        if self.ply() != 0 || self.pawns[0] == self.pawns[1] {
+            return None;
+        }
+        let (version, jack) = match self.card(self.pawns[0]) {
+            0 => (CollapsiVersion::V1_1, 0),
+            1 => (CollapsiVersion::V1_3, 1),
+            _ => return None,
+        };
+        let mut counts = [0usize; 5];
+        for card in self.cards.iter().flatten() {
+            counts[*card as usize] += 1;
+        }
+        counts[jack] = counts[jack].checked_sub(2)?;
+        let legal = self.card(self.pawns[1]) == jack as u8 && counts == [0, 4, 4, 4, 2];
+        legal.then_some(version)
+    }
+
+    /// The representative in `Deals` that this starting deal is equivalent
+    /// to, and the symmetry that maps this deal onto it, or None if it is not
+    /// a legal starting deal
+    ///
+    /// If several symmetries map the deal into `Deals`, the one giving the
+    /// lowest deal ID is used.
+    pub fn canonical_deal(&self) -> Option<(Board, Symmetry)> {
+        let deals = Deals::new(self.deal_version()?);
+        Symmetry::all()
+            .filter(|symmetry| symmetry.apply(self.pawns[0]) == Point(0, 0))
+            .filter(|symmetry| {
+                let Point(x, y) = symmetry.apply(self.pawns[1]);
+                BLUE_JACKS.iter().any(|(p, _)| *p == x * SIZE + y)
+            })
+            .map(|symmetry| (symmetry.apply_to_board(self), symmetry))
+            .filter_map(|(board, symmetry)| Some((deals.index_of(&board)?, board, symmetry)))
+            .min_by_key(|(id, _, _)| *id)
+            .map(|(_, board, symmetry)| (board, symmetry))
+    }
+
+    /// Every symmetry that maps this starting deal onto a representative in
+    /// `Deals`, with that representative's ID and weight
+    pub fn representative_matches(&self) -> Vec<(u64, u64, Symmetry)> {
+        let Some(version) = self.deal_version() else {
+            return vec![];
+        };
+        let deals = Deals::new(version);
+        (0..8)
+            .map(|dihedral| Symmetry {
+                shift: self.pawns[0],
+                dihedral,
+            })
+            .filter_map(|symmetry| {
+                let id = deals.index_of(&symmetry.apply_to_board(self))?;
+                let (_, weight) = BLUE_JACKS[(id / LAYOUTS) as usize];
+                Some((id, weight, symmetry))
+            })
+            .collect()
+    }
+
+    /// Check that this starting deal is accounted for correctly by `Deals`
+    ///
+    /// The deal can be any legal starting deal, shifted, rotated or reflected
+    /// in any way: the 8 symmetries tried first shift its red jack to the
+    /// top-left, wherever it is, and then rotate or reflect about it; see
+    /// `WeightCheck`.
+    ///
+    /// If `solve` is true, this also checks that the deal has the same score
+    /// as its representative, and that the representative's best move works
+    /// here when translated back.
+    pub fn check_symmetry_weights(&self, solve: bool) -> WeightCheck {
+        let matches = self.representative_matches();
+        let mut check = WeightCheck {
+            deals: 1,
+            unmatched: matches.is_empty() as u64,
+            wrong_weight: matches
+                .iter()
+                .any(|(_, weight, _)| *weight * matches.len() as u64 != 8)
+                as u64,
+            ..Default::default()
+        };
+        if solve && !matches.is_empty() {
+            let mut board = self.clone();
+            let (_, score) = board.best_move_by_cards_remaining();
+            let (m, representative_score) = self
+                .best_move_via_representative()
+                .expect("This deal matched a representative");
+            let achieved = match m {
+                Some(m) => board.compare_move_with_exact(m).heuristic_move_score,
+                None => score,
+            };
+            check.solved = 1;
+            check.wrong_score = (score != representative_score || achieved != score) as u64;
+        }
+        check
+    }
+
+    /// Like `best_move_by_cards_remaining`, but by solving this starting
+    /// deal's representative in `Deals` and translating the move back
+    ///
+    /// Return value is None if this is not a legal starting deal.
+    pub fn best_move_via_representative(&self) -> Option<(Option<Point>, i8)> {
+        let result = self.best_move_via_representative_limited(SearchOptions::default())?;
+        Some(result.value)
+    }
+
+    /// Like `best_move_via_representative`, but within the given limits
+    ///
+    /// The result is as for `best_move_by_cards_remaining_limited` on the
+    /// representative, with the move translated back to this deal.
+    pub fn best_move_via_representative_limited(
+        &self,
+        options: SearchOptions,
+    ) -> Option<SearchResult<(Option<Point>, i8)>> {
+        let (mut canonical, symmetry) = self.canonical_deal()?;
+        let mut result = canonical.best_move_by_cards_remaining_limited(options);
+        result.value.0 = result.value.0.map(|m| symmetry.invert(m));
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampled_deals_have_correct_weights() {
+        for version in [CollapsiVersion::V1_1, CollapsiVersion::V1_3] {
+            let deals = Deals::new(version);
+            let check = (0..deals.unreduced_len())
+                .step_by(99_991)
+                .map(|i| {
+                    deals
+                        .get_unreduced(i)
+                        .unwrap()
+                        .check_symmetry_weights(false)
+                })
+                .fold(WeightCheck::default(), WeightCheck::merge);
+            assert_eq!(check.deals, deals.unreduced_len().div_ceil(99_991));
+            assert!(check.passed(), "{}", check);
+        }
+    }
+
+    #[test]
+    fn representatives_solve_like_their_deals() {
+        let deals = Deals::new(CollapsiVersion::V1_3);
+        let check = [0, 5_000_000, 40_000_000]
+            .into_iter()
+            .map(|i| deals.get_unreduced(i).unwrap().check_symmetry_weights(true))
+            .fold(WeightCheck::default(), WeightCheck::merge);
+        assert_eq!(check.solved, 3);
+        assert!(check.passed(), "{}", check);
+    }
+
+    /// Every deal with the red jack at the top-left, which takes several
+    /// minutes: run with `cargo test -r -- --ignored`
+    #[test]
+    #[ignore]
+    #[cfg(feature = "parallel")]
+    fn all_deals_have_correct_weights() {
+        use rayon::prelude::*;
+        use std::sync::atomic::{AtomicU8, Ordering};
+
+        let deals = Deals::new(CollapsiVersion::V1_3);
+        let hits: Vec<AtomicU8> = (0..deals.len()).map(|_| AtomicU8::new(0)).collect();
+        let check = (0..deals.unreduced_len())
+            .into_par_iter()
+            .map(|i| {
+                let board = deals.get_unreduced(i).unwrap();
+                for (id, _, _) in board.representative_matches() {
+                    hits[id as usize].fetch_add(1, Ordering::Relaxed);
+                }
+                board.check_symmetry_weights(false)
+            })
+            .reduce(WeightCheck::default, WeightCheck::merge);
+        assert!(check.passed(), "{}", check);
+        assert!(hits.iter().all(|hits| hits.load(Ordering::Relaxed) == 8));
+    }
+}
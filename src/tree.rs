@@ -0,0 +1,322 @@
+//! Counting and measuring the game tree, exploring each distinct position only
+//! once
+
+use crate::Board;
+use std::collections::HashMap;
+
+/// The size and shape of a game tree, counted over distinct positions
+///
+/// Plies are counted from the position where counting started.
+#[derive(Clone, Debug, Default)]
+pub struct GameTreeCounts {
+    /// Number of possible games, i.e. leaves of the (unmerged) game tree
+    pub games: u64,
+
+    /// Number of distinct positions reachable, including the start
+    pub positions: u64,
+
+    /// Number of distinct finished positions, indexed by the number of plies
+    /// taken to reach them
+    pub terminal_positions_by_length: Vec<u64>,
+
+    /// Number of distinct positions at each ply
+    pub positions_by_ply: Vec<u64>,
+
+    /// Total legal moves from the distinct positions at each ply
+    pub moves_by_ply: Vec<u64>,
+}
+
+impl GameTreeCounts {
+    /// Average number of legal moves from the distinct positions at a ply
+    pub fn average_branching_factor(&self, ply: usize) -> f64 {
+        match self.positions_by_ply.get(ply) {
+            Some(&positions) if positions > 0 => self.moves_by_ply[ply] as f64 / positions as f64,
+            _ => 0.0,
+        }
+    }
+
+    /// Combine with the counts for another tree, multiplying theirs by
+    /// `weight` (for example, the number of deals a board represents)
+    pub fn add_weighted(mut self, other: &GameTreeCounts, weight: u64) -> GameTreeCounts {
+        self.games += other.games * weight;
+        self.positions += other.positions * weight;
+        for (mine, theirs) in [
+            (
+                &mut self.terminal_positions_by_length,
+                &other.terminal_positions_by_length,
+            ),
+            (&mut self.positions_by_ply, &other.positions_by_ply),
+            (&mut self.moves_by_ply, &other.moves_by_ply),
+        ] {
+            add_weighted(mine, theirs, weight);
+        }
+        self
+    }
+
+    /// Record a newly-found distinct position
+    fn record(&mut self, ply: usize, moves: usize) {
+        for list in [
+            &mut self.terminal_positions_by_length,
+            &mut self.positions_by_ply,
+            &mut self.moves_by_ply,
+        ] {
+            if list.len() <= ply {
+                list.resize(ply + 1, 0);
+            }
+        }
+        self.positions += 1;
+        self.positions_by_ply[ply] += 1;
+        self.moves_by_ply[ply] += moves as u64;
+        if moves == 0 {
+            self.terminal_positions_by_length[ply] += 1;
+        }
+    }
+}
+
+/// The shape of a game tree, counted over all possible games rather than
+/// distinct positions
+///
+/// A position that can be reached by several sequences of moves is counted
+/// once for each sequence. Plies are counted from the position where counting
+/// started.
+#[derive(Clone, Debug, Default)]
+pub struct TreeStats {
+    /// Number of possible games, indexed by their length in plies
+    pub games_by_length: Vec<u64>,
+
+    /// Number of possible games won by red (P0) and blue (P1)
+    pub wins: [u64; 2],
+
+    /// For each ply, the number of positions in the tree indexed by how many
+    /// legal moves the player to move has
+    pub branching_by_ply: Vec<Vec<u64>>,
+
+    /// Number of positions in the tree where the player to move has exactly
+    /// one legal move
+    pub forced_positions: u64,
+
+    /// Number of distinct positions where the player to move has exactly one
+    /// legal move
+    pub forced_distinct_positions: u64,
+}
+
+impl TreeStats {
+    /// Total number of possible games
+    pub fn games(&self) -> u64 {
+        self.games_by_length.iter().sum()
+    }
+
+    /// Fraction of possible games won by the given player (0 or 1)
+    pub fn win_fraction(&self, player: usize) -> f64 {
+        self.wins[player] as f64 / self.games() as f64
+    }
+
+    /// Combine with the statistics for another tree, multiplying theirs by
+    /// `weight` (for example, the number of deals a board represents)
+    pub fn add_weighted(mut self, other: &TreeStats, weight: u64) -> TreeStats {
+        add_weighted(&mut self.games_by_length, &other.games_by_length, weight);
+        for player in 0..2 {
+            self.wins[player] += other.wins[player] * weight;
+        }
+        if self.branching_by_ply.len() < other.branching_by_ply.len() {
+            self.branching_by_ply
+                .resize(other.branching_by_ply.len(), vec![]);
+        }
+        for (mine, theirs) in self
+            .branching_by_ply
+            .iter_mut()
+            .zip(&other.branching_by_ply)
+        {
+            add_weighted(mine, theirs, weight);
+        }
+        self.forced_positions += other.forced_positions * weight;
+        self.forced_distinct_positions += other.forced_distinct_positions * weight;
+        self
+    }
+}
+
+/// Add `weight` times each entry of `theirs` to `mine`, extending it if needed
+fn add_weighted(mine: &mut Vec<u64>, theirs: &[u64], weight: u64) {
+    if mine.len() < theirs.len() {
+        mine.resize(theirs.len(), 0);
+    }
+    for (m, t) in mine.iter_mut().zip(theirs) {
+        *m += t * weight;
+    }
+}
+
+impl Board {
+    /// Measure the whole game tree from this position
+    ///
+    /// The tree is explored one ply at a time, keeping each distinct position
+    /// once along with the number of sequences of moves that reach it.
+    pub fn tree_stats(&self) -> TreeStats {
+        let mut stats = TreeStats::default();
+        let mut layer: HashMap<u64, (Board, u64)> =
+            HashMap::from([(self.key(), (self.clone(), 1))]);
+        let mut ply = 0;
+        while !layer.is_empty() {
+            stats.branching_by_ply.push(vec![]);
+            let mut next: HashMap<u64, (Board, u64)> = HashMap::new();
+            for (_, (mut board, sequences)) in layer {
+                let moves = board.legal_moves();
+                let branching = &mut stats.branching_by_ply[ply];
+                if branching.len() <= moves.len() {
+                    branching.resize(moves.len() + 1, 0);
+                }
+                branching[moves.len()] += sequences;
+                match moves.len() {
+                    0 => {
+                        if stats.games_by_length.len() <= ply {
+                            stats.games_by_length.resize(ply + 1, 0);
+                        }
+                        stats.games_by_length[ply] += sequences;
+                        stats.wins[1 - board.turn] += sequences;
+                    }
+                    1 => {
+                        stats.forced_positions += sequences;
+                        stats.forced_distinct_positions += 1;
+                    }
+                    _ => (),
+                }
+                for m in moves {
+                    board.make_move(m);
+                    next.entry(board.key())
+                        .or_insert_with(|| (board.clone(), 0))
+                        .1 += sequences;
+                    board.undo_move();
+                }
+            }
+            layer = next;
+            ply += 1;
+        }
+        stats
+    }
+
+    /// Count the possible games from this position, along with the number of
+    /// distinct positions and the branching factor at each ply
+    ///
+    /// The number of games is the same as `number_of_possible_games`, but each
+    /// distinct position is only explored once, since the number of games
+    /// from a position does not depend on how it was reached.
+    pub fn count_game_tree(&mut self) -> GameTreeCounts {
+        let mut counts = GameTreeCounts::default();
+        let start = self.moves.len();
+        counts.games = self.count_game_tree_memoised(start, &mut HashMap::new(), &mut counts);
+        counts
+    }
+
+    /// Number of games from this position, looking up and storing results in
+    /// `memo` by `Board::key`
+    ///
+    /// This is the recursive function that powers `count_game_tree`.
+    fn count_game_tree_memoised(
+        &mut self,
+        start: usize,
+        memo: &mut HashMap<u64, u64>,
+        counts: &mut GameTreeCounts,
+    ) -> u64 {
+        let key = self.key();
+        if let Some(games) = memo.get(&key) {
+            return *games;
+        }
+        let moves = self.legal_moves();
+        counts.record(self.moves.len() - start, moves.len());
+        let games = if moves.is_empty() {
+            1
+        } else {
+            moves
+                .into_iter()
+                .map(|m| {
+                    self.make_move(m);
+                    let games = self.count_game_tree_memoised(start, memo, counts);
+                    self.undo_move();
+                    games
+                })
+                .sum()
+        };
+        memo.insert(key, games);
+        games
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TreeStats;
+    use crate::{Board, CollapsiVersion};
+    use std::collections::HashSet;
+
+    /// Add every game from this position to `stats`, one sequence of moves at
+    /// a time, collecting the keys of forced positions in `forced`
+    fn walk(board: &mut Board, ply: usize, stats: &mut TreeStats, forced: &mut HashSet<u64>) {
+        let moves = board.legal_moves();
+        if stats.branching_by_ply.len() <= ply {
+            stats.branching_by_ply.resize(ply + 1, vec![]);
+        }
+        let branching = &mut stats.branching_by_ply[ply];
+        if branching.len() <= moves.len() {
+            branching.resize(moves.len() + 1, 0);
+        }
+        branching[moves.len()] += 1;
+        if moves.is_empty() {
+            if stats.games_by_length.len() <= ply {
+                stats.games_by_length.resize(ply + 1, 0);
+            }
+            stats.games_by_length[ply] += 1;
+            stats.wins[1 - board.turn] += 1;
+        }
+        if moves.len() == 1 {
+            stats.forced_positions += 1;
+            forced.insert(board.key());
+        }
+        for m in moves {
+            board.make_move(m);
+            walk(board, ply + 1, stats, forced);
+            board.undo_move();
+        }
+    }
+
+    #[test]
+    fn counted_games_match_full_walk() {
+        let boards = [
+            Board::example(),
+            Board::from_deal_id(CollapsiVersion::V1_3, 0).unwrap(),
+            Board::from_deal_id(CollapsiVersion::V1_3, 1_000_000).unwrap(),
+        ];
+        for mut board in boards {
+            let counts = board.count_game_tree();
+            assert_eq!(counts.games, board.number_of_possible_games());
+            assert_eq!(counts.games, board.tree_stats().games());
+            assert_eq!(
+                counts.positions,
+                counts.positions_by_ply.iter().sum::<u64>()
+            );
+            assert_eq!(counts.positions_by_ply[0], 1);
+        }
+    }
+
+    #[test]
+    fn tree_stats_match_brute_force_walk() {
+        // The example board after a few plies of perfect play
+        let mut board = Board::example();
+        for m in board.perfect_game().into_iter().take(4) {
+            board.make_move(m);
+        }
+        let stats = board.tree_stats();
+        let mut expected = TreeStats::default();
+        let mut forced = HashSet::new();
+        walk(&mut board, 0, &mut expected, &mut forced);
+        expected.forced_distinct_positions = forced.len() as u64;
+
+        assert!(expected.games() > 1);
+        assert!(expected.forced_positions > expected.forced_distinct_positions);
+        assert_eq!(stats.wins, expected.wins);
+        assert_eq!(stats.games_by_length, expected.games_by_length);
+        assert_eq!(stats.branching_by_ply, expected.branching_by_ply);
+        assert_eq!(stats.forced_positions, expected.forced_positions);
+        assert_eq!(
+            stats.forced_distinct_positions,
+            expected.forced_distinct_positions
+        );
+    }
+}
@@ -0,0 +1,217 @@
+//! A wasm-bindgen API for playing and analysing games from JavaScript
+//!
+//! Everything is accessed through a `Game`, and results are returned as plain
+//! JavaScript objects (which can be passed straight to `JSON.stringify`).
+//! Scores are as for `Board::best_move_by_cards_remaining`.
+
+use crate::{Board, CollapsiVersion, MovePath, Point};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+/// A square on the board, as sent to JavaScript
+#[derive(Serialize)]
+pub struct Square {
+    pub row: usize,
+    pub col: usize,
+}
+
+impl From<Point> for Square {
+    fn from(Point(row, col): Point) -> Self {
+        Square { row, col }
+    }
+}
+
+/// Everything needed to draw a position
+#[derive(Serialize)]
+pub struct Position {
+    /// The position in the notation read by `Game::new`
+    pub notation: String,
+
+    /// Card values by row and column, with 0 for a flipped card (or a joker
+    /// in the old rules)
+    pub cards: Vec<Vec<u8>>,
+
+    /// Red's pawn, then blue's
+    pub pawns: [Square; 2],
+
+    /// The player to move: 0 for red, 1 for blue
+    pub turn: usize,
+
+    /// The number of plies made since the start of the game
+    pub ply: usize,
+
+    pub legal_moves: Vec<Square>,
+
+    /// The final score if the game is over
+    pub score: Option<i8>,
+}
+
+/// A path a pawn can take, as the squares visited from the pawn's square to
+/// the destination, and whether each step wraps around an edge of the grid
+#[derive(Serialize)]
+pub struct Path {
+    pub squares: Vec<Square>,
+    pub wraps: Vec<bool>,
+}
+
+impl From<MovePath> for Path {
+    fn from(path: MovePath) -> Self {
+        Path {
+            squares: path.squares.into_iter().map(Square::from).collect(),
+            wraps: path.wraps,
+        }
+    }
+}
+
+/// Every path to one legal move
+#[derive(Serialize)]
+pub struct MovePaths {
+    pub row: usize,
+    pub col: usize,
+    pub paths: Vec<Path>,
+}
+
+/// A perfect-play move and the score it achieves
+#[derive(Serialize)]
+pub struct Solution {
+    /// None if the game is over
+    pub best_move: Option<Square>,
+    pub score: i8,
+}
+
+/// The score achieved by one legal move, with perfect play afterwards
+#[derive(Serialize)]
+pub struct MoveEvaluation {
+    pub row: usize,
+    pub col: usize,
+    pub score: i8,
+}
+
+/// A game in progress
+#[wasm_bindgen]
+pub struct Game {
+    board: Board,
+}
+
+#[wasm_bindgen]
+impl Game {
+    /// Read a position from its notation, for example
+    /// "1223/4121r/3123/1b314/0"
+    #[wasm_bindgen(constructor)]
+    pub fn new(notation: &str) -> Result<Game, JsError> {
+        Ok(Game {
+            board: Board::new(notation).map_err(JsError::new)?,
+        })
+    }
+
+    /// A random starting deal, with the old rules (v1.1) if `old` is true
+    pub fn random(seed: u32, old: bool) -> Game {
+        let version = if old {
+            CollapsiVersion::V1_1
+        } else {
+            CollapsiVersion::V1_3
+        };
+        let mut rng = StdRng::seed_from_u64(seed as u64);
+        Game {
+            board: Board::random_deal(version, &mut rng),
+        }
+    }
+
+    /// The current position in notation
+    pub fn notation(&self) -> String {
+        self.board.notation()
+    }
+
+    /// The current position, as a `Position` object
+    pub fn position(&self) -> Result<JsValue, JsError> {
+        let legal_moves = self.board.legal_moves();
+        let position = Position {
+            notation: self.board.notation(),
+            cards: self.board.cards.iter().map(|row| row.to_vec()).collect(),
+            pawns: self.board.pawns.map(Square::from),
+            turn: self.board.turn,
+            ply: self.board.ply(),
+            score: legal_moves.is_empty().then(|| self.board.final_score()),
+            legal_moves: legal_moves.into_iter().map(Square::from).collect(),
+        };
+        to_js(&position)
+    }
+
+    /// The squares the player to move can move to, as `Square` objects
+    pub fn legal_moves(&self) -> Result<JsValue, JsError> {
+        let moves: Vec<Square> = self
+            .board
+            .legal_moves()
+            .into_iter()
+            .map(Square::from)
+            .collect();
+        to_js(&moves)
+    }
+
+    /// Every path to each legal move, as `MovePaths` objects
+    pub fn move_paths(&self) -> Result<JsValue, JsError> {
+        let moves: Vec<MovePaths> = self
+            .board
+            .move_paths()
+            .into_iter()
+            .map(|(Point(row, col), paths)| MovePaths {
+                row,
+                col,
+                paths: paths.into_iter().map(Path::from).collect(),
+            })
+            .collect();
+        to_js(&moves)
+    }
+
+    /// Move the player to move to the given square
+    pub fn make_move(&mut self, row: usize, col: usize) -> Result<(), JsError> {
+        let point = Point::new(row, col).map_err(JsError::new)?;
+        if !self.board.legal_moves().contains(&point) {
+            return Err(JsError::new("Illegal move"));
+        }
+        self.board.make_move(point);
+        Ok(())
+    }
+
+    /// Take back the last move made with `make_move`
+    pub fn undo_move(&mut self) -> Result<(), JsError> {
+        if self.board.moves.is_empty() {
+            return Err(JsError::new("No moves to undo"));
+        }
+        self.board.undo_move();
+        Ok(())
+    }
+
+    /// A perfect-play move, as a `Solution` object
+    pub fn solve(&mut self) -> Result<JsValue, JsError> {
+        let (best_move, score) = self.board.best_move_by_cards_remaining();
+        to_js(&Solution {
+            best_move: best_move.map(Square::from),
+            score,
+        })
+    }
+
+    /// The exact score of every legal move, as `MoveEvaluation` objects
+    pub fn evaluate_moves(&mut self) -> Result<JsValue, JsError> {
+        let evaluations: Vec<MoveEvaluation> = self
+            .board
+            .legal_moves()
+            .into_iter()
+            .map(|point| {
+                self.board.make_move(point);
+                let (_, score) = self.board.best_move_by_cards_remaining();
+                self.board.undo_move();
+                let Point(row, col) = point;
+                MoveEvaluation { row, col, score }
+            })
+            .collect();
+        to_js(&evaluations)
+    }
+}
+
+/// Convert a value to a plain JavaScript object
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsError> {
+    Ok(serde_wasm_bindgen::to_value(value)?)
+}
@@ -0,0 +1,72 @@
+/* Exercises the C API; compiled and run by tests/ffi.rs */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "collapsi.h"
+
+#define CHECK(condition)                                                   \
+  do {                                                                     \
+    if (!(condition)) {                                                    \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__,    \
+              #condition);                                                 \
+      return 1;                                                            \
+    }                                                                      \
+  } while (0)
+
+int main(void) {
+  char notation[64];
+  CollapsiSquare moves[16];
+  CollapsiSquare best;
+  int8_t score;
+
+  CHECK(collapsi_board_new("1223/4121/3123/1b314/0") == NULL);
+  CHECK(collapsi_board_from_deal_id(99999999, false) == NULL);
+
+  CollapsiBoard *board = collapsi_board_new("1223/4121r/3123/1b314/0");
+  CHECK(board != NULL);
+  CHECK(collapsi_board_turn(board) == 0);
+  CHECK(collapsi_board_notation(board, notation, sizeof notation) == 23);
+  CHECK(strcmp(notation, "1223/4121r/3123/1b314/0") == 0);
+  CHECK(collapsi_board_notation(board, notation, 5) == 23);
+  CHECK(strcmp(notation, "1223") == 0);
+
+  CHECK(collapsi_legal_moves(board, moves, 16) == 4);
+  CHECK(moves[1].row == 1 && moves[1].col == 0);
+  CHECK(collapsi_legal_moves(board, moves, 1) == 4);
+
+  CollapsiSquare wrap[] = {{1, 3}, {1, 0}};
+  CollapsiSquare jump[] = {{1, 3}, {1, 1}};
+  CHECK(collapsi_check_path(board, wrap, 2));
+  CHECK(!collapsi_check_path(board, jump, 2));
+  CHECK(!collapsi_check_path(board, wrap, 1));
+
+  CHECK(!collapsi_make_move(board, 1, 3));
+  CHECK(!collapsi_make_move(board, 9, 9));
+  CHECK(collapsi_make_move(board, 1, 0));
+  CHECK(collapsi_board_turn(board) == 1);
+  collapsi_board_notation(board, notation, sizeof notation);
+  CHECK(strcmp(notation, "1223/4r120/3123/1b314/1") == 0);
+  CHECK(collapsi_undo_move(board));
+  CHECK(!collapsi_undo_move(board));
+
+  CHECK(collapsi_solve(board, &best, &score));
+  CHECK(score == 3);
+  CHECK(collapsi_make_move(board, best.row, best.col));
+  CHECK(collapsi_solve(board, NULL, &score));
+  CHECK(score == 3);
+  collapsi_board_free(board);
+
+  board = collapsi_board_from_deal_id(12148512, false);
+  CHECK(board != NULL);
+  collapsi_board_notation(board, notation, sizeof notation);
+  CHECK(strcmp(notation, "1r343/411b3/1231/2212/0") == 0);
+  collapsi_board_free(board);
+
+  CHECK(collapsi_board_turn(NULL) == -1);
+  CHECK(!collapsi_solve(NULL, &best, &score));
+  collapsi_board_free(NULL);
+
+  printf("ok\n");
+  return 0;
+}
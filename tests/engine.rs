@@ -0,0 +1,201 @@
+//! Tests of the UCI-style engine protocol, scripting commands and reading the
+//! replies
+
+use collapsi::engine::Engine;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+const EXAMPLE: &str = "1223/4121r/3123/1b314/0";
+
+/// Output shared between the engine and the test
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Run a script of commands, returning every line of output
+fn run(script: &[&str]) -> Vec<String> {
+    let output = Output::default();
+    let mut engine = Engine::new(output.clone());
+    engine.run(script.join("\n").as_bytes());
+    let text = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+    text.lines().map(str::to_string).collect()
+}
+
+/// The move given by the last `bestmove` line
+fn best_move(lines: &[String]) -> &str {
+    lines
+        .iter()
+        .rev()
+        .find_map(|line| line.strip_prefix("bestmove "))
+        .expect("The search reports a best move")
+}
+
+#[test]
+fn handshake() {
+    let lines = run(&["uci", "isready"]);
+    assert_eq!(lines.first().unwrap(), "id name Collapsi solver");
+    assert_eq!(&lines[lines.len() - 2..], ["uciok", "readyok"]);
+}
+
+#[test]
+fn solves_the_example() {
+    let lines = run(&[&format!("position {}", EXAMPLE), "go"]);
+    let info: Vec<&String> = lines
+        .iter()
+        .filter(|line| line.starts_with("info"))
+        .collect();
+    assert_eq!(info.len(), 5);
+    for (d, line) in info[..4].iter().enumerate() {
+        assert!(
+            line.starts_with(&format!("info depth {} score ", d + 1)),
+            "{}",
+            line
+        );
+    }
+    let exact = info[4];
+    assert!(exact.contains(" score cards 3 "), "{}", exact);
+    assert!(exact.contains(" pv 10 "), "{}", exact);
+    assert_eq!(best_move(&lines), "10");
+}
+
+#[test]
+fn depth_limits_the_search_to_the_heuristic() {
+    let lines = run(&[&format!("position {} moves 10", EXAMPLE), "go depth 2"]);
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("info depth 1 score "));
+    assert!(lines[1].starts_with("info depth 2 score "));
+    assert!(lines[2].starts_with("bestmove "));
+}
+
+#[test]
+fn scores_are_for_the_player_to_move() {
+    // After red's winning move, blue is to move and loses
+    let lines = run(&[&format!("position {} moves 10", EXAMPLE), "go"]);
+    let exact = lines
+        .iter()
+        .find(|line| line.contains("score cards"))
+        .unwrap();
+    assert!(exact.contains(" score cards -3 "), "{}", exact);
+}
+
+#[test]
+fn node_limit_stops_both_stages() {
+    let lines = run(&["position deal 0", "go nodes 1"]);
+    assert!(lines.iter().all(|line| !line.starts_with("info depth")));
+    assert!(
+        lines
+            .iter()
+            .any(|line| line.ends_with("exact search stopped"))
+    );
+    assert_ne!(best_move(&lines), "none");
+}
+
+#[test]
+fn move_time_stops_both_stages() {
+    let lines = run(&["position deal 0", "go movetime 0"]);
+    assert!(lines.iter().all(|line| !line.starts_with("info depth")));
+    assert_ne!(best_move(&lines), "none");
+}
+
+#[test]
+fn stop_still_reports_a_best_move() {
+    let output = Output::default();
+    let mut engine = Engine::new(output.clone());
+    engine.command("position deal 0");
+    engine.command("go infinite");
+    engine.command("stop");
+    let text = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<String> = text.lines().map(str::to_string).collect();
+    assert_eq!(
+        lines
+            .iter()
+            .filter(|line| line.starts_with("bestmove"))
+            .count(),
+        1
+    );
+    assert_ne!(best_move(&lines), "none");
+}
+
+#[test]
+fn node_limit_covers_the_principal_variation() {
+    let nodes = |line: &str| -> u64 {
+        let (_, rest) = line.split_once(" nodes ").unwrap();
+        rest.split(' ').next().unwrap().parse().unwrap()
+    };
+    let pv = |line: &str| line.split_once(" pv ").unwrap().1.split(' ').count();
+    let exact = |lines: &[String]| -> String {
+        lines
+            .iter()
+            .find(|line| line.contains(" score cards "))
+            .unwrap()
+            .clone()
+    };
+    let full = exact(&run(&[&format!("position {}", EXAMPLE), "go"]));
+
+    // Enough to solve the position, but not to follow the line of perfect
+    // play to the end of the game
+    let limit = nodes(&full) * 3 / 4;
+    let lines = run(&[
+        &format!("position {}", EXAMPLE),
+        &format!("go nodes {}", limit),
+    ]);
+    let limited = exact(&lines);
+    assert!(nodes(&limited) <= limit + 1, "{}", limited);
+    assert!(pv(&limited) < pv(&full), "{}", limited);
+    assert_eq!(best_move(&lines), "10");
+}
+
+#[test]
+fn position_stops_an_infinite_search() {
+    let output = Output::default();
+    let mut engine = Engine::new(output.clone());
+    engine.command("position deal 0");
+    engine.command("go infinite");
+    engine.command(&format!("position {}", EXAMPLE));
+    engine.command("d");
+    let text = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(
+        lines
+            .iter()
+            .filter(|line| line.starts_with("bestmove"))
+            .count(),
+        1
+    );
+    assert_eq!(lines.last().unwrap(), &EXAMPLE);
+}
+
+#[test]
+fn finished_games_have_no_best_move() {
+    let lines = run(&["position 0000/01r00/0000/1b000/14", "go"]);
+    assert_eq!(lines, ["bestmove none"]);
+}
+
+#[test]
+fn new_game_returns_to_the_example() {
+    let lines = run(&[&format!("position {} moves 10", EXAMPLE), "ucinewgame", "d"]);
+    assert_eq!(lines.last().unwrap(), EXAMPLE);
+}
+
+#[test]
+fn bad_commands_are_reported() {
+    let lines = run(&[
+        "position 1223",
+        &format!("position {} moves 11", EXAMPLE),
+        "go depth x",
+        "setoption name Colour value red",
+        "fly",
+    ]);
+    assert_eq!(lines.len(), 5);
+    assert!(lines.iter().all(|line| line.starts_with("info string ")));
+}
@@ -0,0 +1,53 @@
+//! Compiles the C program in tests/c/test.c against the C API and runs it
+
+#![cfg(all(feature = "ffi", unix))]
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn c_program() {
+    // The cdylib is built alongside the rlib the test links against, in deps/
+    let exe = env::current_exe().unwrap();
+    let lib_dir = exe.parent().unwrap();
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let program = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("collapsi-c-test");
+
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let compiled = match Command::new(&compiler)
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg(manifest_dir.join("tests/c/test.c"))
+        .arg("-o")
+        .arg(&program)
+        .arg("-L")
+        .arg(lib_dir)
+        .arg("-lcollapsi")
+        .status()
+    {
+        Ok(status) => status,
+        Err(error) => panic!(
+            "Could not run the C compiler {:?} ({}); install one, set CC, or skip this test with `cargo test -- --skip c_program`",
+            compiler, error
+        ),
+    };
+    assert!(compiled.success(), "The C program compiles");
+
+    // Cargo's library path includes target/debug, which may hold an older
+    // build of the library, so look in deps/ first
+    let output = Command::new(&program)
+        .env("LD_LIBRARY_PATH", lib_dir)
+        .env("DYLD_LIBRARY_PATH", lib_dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "The C program passes: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+}
@@ -0,0 +1,49 @@
+//! Tests that positions are written with the macros as in paper/paper.tex
+
+use collapsi::latex::LatexOptions;
+use collapsi::{Board, Point};
+
+#[test]
+fn example_board_as_in_the_paper() {
+    let board = Board::new("1223/4121r/3123/1b314/0").unwrap();
+    assert_eq!(
+        board.to_latex(&LatexOptions::default()),
+        r"\board A223 4A2{\redpawn} 3A23 {\bluepawn}3A4"
+    );
+}
+
+#[test]
+fn win_in_seven_as_in_the_paper() {
+    let board = Board::new("1r341/11b12/2123/3234/0").unwrap();
+    assert_eq!(
+        board.to_latex(&LatexOptions { ace: '1' }),
+        r"\board {\redpawn}341 1{\bluepawn}12 2123 3234"
+    );
+}
+
+#[test]
+fn face_down_cards_use_the_custom_board() {
+    let board = Board::new("1223/4r120/3123/1b314/1").unwrap();
+    let latex = board.to_latex(&LatexOptions::default());
+    assert!(latex.starts_with("\\customboard\n"));
+    assert_eq!(latex.matches(r"{\facedown}").count(), 1);
+    assert!(latex.contains(r"{\card{4\redpawn}}"));
+    assert!(latex.contains(r"{\card{\bluepawn}}"));
+}
+
+#[test]
+fn figures_reject_illegal_moves() {
+    let board = Board::new("1223/4121r/3123/1b314/0").unwrap();
+    let options = LatexOptions::default();
+    let figures = board
+        .to_latex_figures(&[Point::new(1, 0).unwrap()], &options)
+        .unwrap();
+    assert_eq!(figures.matches("\\begin{figure}").count(), 2);
+    assert!(figures.contains(r"\caption{Ply 1: red moves to $(1,0)$}"));
+
+    let illegal = [Point::new(0, 0).unwrap()];
+    assert_eq!(
+        board.to_latex_figures(&illegal, &options),
+        Err("Illegal move in the game")
+    );
+}
@@ -0,0 +1,78 @@
+//! Tests of proofs: writing them out, reading them back and checking them
+
+use collapsi::proof::Proof;
+use collapsi::search::SearchOptions;
+use collapsi::{Board, Point};
+
+const EXAMPLE: &str = "1223/4121r/3123/1b314/0";
+
+/// A proof of the result of the example board
+fn example_proof() -> (Board, Proof) {
+    let mut board = Board::new(EXAMPLE).unwrap();
+    let result = board.prove(SearchOptions::default());
+    assert!(result.completed);
+    (board, result.value.expect("Search has no limits"))
+}
+
+#[test]
+fn proof_survives_text_round_trip() {
+    let (mut board, proof) = example_proof();
+    assert!(proof.verify(&mut board));
+    let text = proof.to_string();
+    let parsed: Proof = text.parse().unwrap();
+    assert_eq!(parsed, proof);
+    assert_eq!(parsed.size(), proof.size());
+    assert!(parsed.verify(&mut board));
+    assert_eq!(board.notation(), EXAMPLE);
+}
+
+#[test]
+fn tampered_proofs_are_rejected() {
+    let (mut board, proof) = example_proof();
+    let Proof::Win(m, reply) = proof.clone() else {
+        panic!("Red wins the example board");
+    };
+    let Proof::Loss(children) = *reply.clone() else {
+        panic!("Wins are followed by losses");
+    };
+    assert!(!children.is_empty());
+
+    // Claiming the opponent cannot move after the winning move
+    let no_replies = Proof::Win(m, Box::new(Proof::Loss(vec![])));
+    assert!(!no_replies.verify(&mut board));
+
+    // Leaving out one of the opponent's replies
+    let missing_reply = Proof::Win(m, Box::new(Proof::Loss(children[1..].to_vec())));
+    assert!(!missing_reply.verify(&mut board));
+
+    // Claiming the player to move loses
+    let swapped = Proof::Loss(vec![(m, *reply)]);
+    assert!(!swapped.verify(&mut board));
+
+    // Changing one character of the text
+    let text = proof.to_string();
+    let mut chars: Vec<char> = text.chars().collect();
+    chars[1] = if chars[1] == '0' { '1' } else { '0' };
+    let tampered: Proof = chars.into_iter().collect::<String>().parse().unwrap();
+    assert!(!tampered.verify(&mut board));
+
+    // A proof for one position is not a proof for another
+    let mut other = Board::new("1223/4121b/3123/1r314/0").unwrap();
+    assert!(!proof.verify(&mut other));
+}
+
+#[test]
+fn malformed_proofs_are_not_read() {
+    for text in [
+        "", "+1", "+12", "+12-(", "-(12+", "+12-()x", "*12-()", "+42-()",
+    ] {
+        assert!(text.parse::<Proof>().is_err(), "{:?}", text);
+    }
+    assert_eq!(
+        "+12-()".parse::<Proof>(),
+        Ok(Proof::Win(
+            Point::new(1, 2).unwrap(),
+            Box::new(Proof::Loss(vec![]))
+        ))
+    );
+}
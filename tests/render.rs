@@ -0,0 +1,83 @@
+//! Tests of the structure of rendered boards
+
+use collapsi::render::RenderOptions;
+use collapsi::{Board, Point};
+
+/// The example board after red's first move, from (1,3) around the right edge
+/// to (1,0), leaving a face-down card behind
+const AFTER_WRAP: &str = "1223/4r120/3123/1b314/1";
+
+fn count(svg: &str, pattern: &str) -> usize {
+    svg.matches(pattern).count()
+}
+
+#[test]
+fn cards_pawns_and_face_down_cards() {
+    let board = Board::new(AFTER_WRAP).unwrap();
+    let svg = board.to_svg(&RenderOptions::default());
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+    assert!(svg.ends_with("</svg>\n"));
+
+    // 16 cards, one of them face down, plus the background and the pattern
+    // for the back of the cards
+    assert_eq!(count(&svg, "<rect"), 16 + 1 + 2);
+    assert_eq!(count(&svg, r#"fill="url(#back)""#), 1);
+    assert_eq!(count(&svg, "<text"), 15);
+    // Blue is still on its jack, while red has moved off its own
+    assert_eq!(count(&svg, ">J</text>"), 1);
+    assert_eq!(count(&svg, "<g fill="), 2);
+    assert_eq!(count(&svg, "<line"), 0);
+}
+
+#[test]
+fn wrapping_arrow_leaves_one_side_and_enters_the_other() {
+    let board = Board::new(AFTER_WRAP).unwrap();
+    let svg = board.to_svg(&RenderOptions {
+        path: vec![Point::new(1, 3).unwrap(), Point::new(1, 0).unwrap()],
+        ..Default::default()
+    });
+    let lines: Vec<&str> = svg
+        .lines()
+        .filter(|line| line.starts_with("<line"))
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert!(!lines[0].contains("marker-end"));
+    assert!(lines[1].contains(r#"marker-end="url(#arrow)""#));
+    // Both halves are on row 1, and the first heads right, off the grid
+    let attribute = |line: &str, name: &str| -> f64 {
+        let start = line.find(&format!(" {}=\"", name)).unwrap() + name.len() + 3;
+        line[start..].split('"').next().unwrap().parse().unwrap()
+    };
+    assert_eq!(attribute(lines[0], "y1"), attribute(lines[0], "y2"));
+    assert!(attribute(lines[0], "x2") > attribute(lines[0], "x1"));
+    assert!(attribute(lines[1], "x2") > attribute(lines[1], "x1"));
+    // In red, since red's pawn is at the end of the path
+    assert!(
+        lines
+            .iter()
+            .all(|line| line.contains(r##"stroke="#d62728""##))
+    );
+}
+
+#[test]
+fn arrow_between_neighbours_does_not_wrap() {
+    let board = Board::example();
+    let svg = board.to_svg(&RenderOptions {
+        path: vec![Point::new(1, 3).unwrap(), Point::new(2, 3).unwrap()],
+        ..Default::default()
+    });
+    assert_eq!(count(&svg, "<line"), 1);
+    assert_eq!(count(&svg, "marker-end"), 1);
+}
+
+#[test]
+fn legal_moves_are_highlighted() {
+    let board = Board::example();
+    let svg = board.to_svg(&RenderOptions {
+        legal_moves: true,
+        scale: 0.5,
+        ..Default::default()
+    });
+    assert_eq!(count(&svg, r#"stroke-width="4""#), 4);
+    assert!(svg.contains(r#"width="208" height="228""#));
+}
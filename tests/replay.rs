@@ -0,0 +1,44 @@
+//! Tests of the slideshows written for replays
+
+use collapsi::replay::Replay;
+use collapsi::{Board, Point};
+
+const EXAMPLE: &str = "1223/4121r/3123/1b314/0";
+
+fn slides(html: &str) -> usize {
+    html.matches("<section class=\"slide\">").count()
+}
+
+#[test]
+fn perfect_play_has_a_slide_per_position() {
+    let board = Board::new(EXAMPLE).unwrap();
+    let moves = board.perfect_game();
+    assert!(!moves.is_empty());
+    let html = Replay::perfect_play(&board).to_html();
+    assert_eq!(slides(&html), moves.len() + 1);
+    // Perfect play keeps the evaluation of the starting position
+    assert_eq!(
+        html.matches("Red wins with 3 cards remaining.").count(),
+        moves.len() + 1
+    );
+    assert_eq!(html.matches("Blue cannot move").count(), 1);
+}
+
+#[test]
+fn traced_game_has_a_slide_per_position() {
+    let board = Board::new(EXAMPLE).unwrap();
+    let moves = vec![Point::new(1, 0).unwrap(), Point::new(3, 1).unwrap()];
+    let html = Replay::new(&board, moves.clone()).unwrap().to_html();
+    assert_eq!(slides(&html), moves.len() + 1);
+    assert!(html.contains("Ply 1: Red moves from (1,3) to (1,0)"));
+    assert!(html.contains("Ply 2: Blue moves from (3,0) to (3,1)"));
+
+    let html = Replay::new(&board, vec![]).unwrap().to_html();
+    assert_eq!(slides(&html), 1);
+}
+
+#[test]
+fn illegal_moves_are_rejected() {
+    let board = Board::new(EXAMPLE).unwrap();
+    assert!(Replay::new(&board, vec![Point::new(0, 0).unwrap()]).is_err());
+}
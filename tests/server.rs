@@ -0,0 +1,159 @@
+//! Tests of the HTTP server, against a real server on localhost
+
+#![cfg(feature = "serve")]
+
+use collapsi::server::{Server, ServerConfig};
+use serde_json::{Value, json};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+const EXAMPLE: &str = "1223/4121r/3123/1b314/0";
+
+/// Start a server on a free port in the background
+fn start(config: ServerConfig) -> SocketAddr {
+    let server = Server::bind(ServerConfig {
+        address: "127.0.0.1:0".to_string(),
+        ..config
+    })
+    .expect("Can bind to localhost");
+    let address = server.local_addr().unwrap();
+    thread::spawn(move || server.run());
+    address
+}
+
+/// Send a request and return the status and JSON body of the response
+fn request(address: SocketAddr, method: &str, path: &str, body: &str) -> (u16, Value) {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let status = response[9..12].parse().unwrap();
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    (status, serde_json::from_str(body).unwrap())
+}
+
+fn board(notation: &str) -> String {
+    json!({ "board": notation }).to_string()
+}
+
+#[test]
+fn endpoints() {
+    let address = start(ServerConfig::default());
+
+    let (status, solution) = request(address, "POST", "/solve", &board(EXAMPLE));
+    assert_eq!(status, 200);
+    assert_eq!(solution["score"], 3);
+    assert_eq!(solution["completed"], true);
+
+    let (_, analysis) = request(address, "POST", "/analyse", &board(EXAMPLE));
+    let scores: Vec<i64> = analysis["moves"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["score"].as_i64().unwrap())
+        .collect();
+    assert_eq!(scores, [-4, 3, -4, -4]);
+
+    let (_, moves) = request(address, "POST", "/legal-moves", &board(EXAMPLE));
+    assert_eq!(moves["moves"][1]["move"], json!({"row": 1, "col": 0}));
+    assert_eq!(moves["moves"][1]["board"], "1223/4r120/3123/1b314/1");
+    assert_eq!(moves["moves"][1]["paths"][0]["wraps"], json!([true]));
+
+    let (status, deal) = request(address, "GET", "/deal/12148512", "");
+    assert_eq!(status, 200);
+    assert_eq!(deal["board"], "1r343/411b3/1231/2212/0");
+}
+
+#[test]
+fn errors() {
+    let address = start(ServerConfig::default());
+    assert_eq!(request(address, "POST", "/solve", "{}").0, 400);
+    assert_eq!(request(address, "POST", "/solve", &board("1223")).0, 400);
+    assert_eq!(request(address, "GET", "/deal/99999999", "").0, 404);
+    assert_eq!(request(address, "GET", "/nowhere", "").0, 404);
+}
+
+#[test]
+fn timeouts_are_reported_and_not_cached() {
+    let server = Server::bind(ServerConfig {
+        address: "127.0.0.1:0".to_string(),
+        timeout: Duration::ZERO,
+        ..Default::default()
+    })
+    .unwrap();
+    // Not a starting deal, so it is searched directly
+    let body = board("1223/4r120/3123/1b314/1");
+    let (status, solution) = server.respond("POST", "/solve", &body);
+    assert_eq!(status, 200);
+    assert_eq!(solution["completed"], false);
+    assert_eq!(server.cache_len(), 0);
+
+    // Asking again searches again rather than repeating the stopped search
+    let (_, solution) = server.respond("POST", "/solve", &body);
+    assert_eq!(solution["completed"], false);
+    assert_eq!(server.cache_hits(), 0);
+}
+
+#[test]
+fn analysis_without_time_has_no_scores() {
+    let server = Server::bind(ServerConfig {
+        address: "127.0.0.1:0".to_string(),
+        timeout: Duration::ZERO,
+        ..Default::default()
+    })
+    .unwrap();
+    let (status, analysis) = server.respond("POST", "/analyse", &board("1r343/411b3/1231/2212/0"));
+    assert_eq!(status, 200);
+    assert_eq!(analysis["completed"], false);
+    let moves = analysis["moves"].as_array().unwrap();
+    assert!(!moves.is_empty());
+    for m in moves {
+        assert_eq!(m["score"], Value::Null, "{}", m);
+        assert_eq!(m["completed"], false);
+    }
+    assert_eq!(server.cache_len(), 0);
+}
+
+#[test]
+fn repeated_requests_are_cached() {
+    let server = Server::bind(ServerConfig {
+        address: "127.0.0.1:0".to_string(),
+        cache_size: 1,
+        ..Default::default()
+    })
+    .unwrap();
+    let first = server.respond("POST", "/solve", &board(EXAMPLE));
+    assert_eq!(first.1["completed"], true);
+    assert_eq!((server.cache_len(), server.cache_hits()), (1, 0));
+    assert_eq!(server.respond("POST", "/solve", &board(EXAMPLE)), first);
+    assert_eq!((server.cache_len(), server.cache_hits()), (1, 1));
+
+    // The oldest response is forgotten to make room
+    server.respond("GET", "/deal/0", "");
+    assert_eq!((server.cache_len(), server.cache_hits()), (1, 1));
+    assert_eq!(server.respond("POST", "/solve", &board(EXAMPLE)), first);
+    assert_eq!((server.cache_len(), server.cache_hits()), (1, 1));
+}
+
+#[test]
+fn too_many_requests_are_turned_away() {
+    let address = start(ServerConfig {
+        max_concurrent: 1,
+        ..Default::default()
+    });
+    // Hold one request open by never finishing it
+    let mut slow = TcpStream::connect(address).unwrap();
+    write!(slow, "POST /solve HTTP/1.1\r\nContent-Length: 100\r\n\r\n").unwrap();
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(request(address, "GET", "/deal/0", "").0, 503);
+}
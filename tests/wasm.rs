@@ -0,0 +1,95 @@
+//! Tests of the JavaScript API, run under node with
+//!
+//! ```
+//! cargo test --target wasm32-unknown-unknown --no-default-features --features wasm
+//! ```
+//!
+//! which needs `wasm-bindgen-cli` installed (at the same version as the
+//! `wasm-bindgen` dependency) to provide the test runner.
+
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+
+use collapsi::wasm::Game;
+use serde_json::{Value, json};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+const EXAMPLE: &str = "1223/4121r/3123/1b314/0";
+
+/// Read a value returned by the API as JSON
+fn json(value: wasm_bindgen::JsValue) -> Value {
+    serde_wasm_bindgen::from_value(value).expect("The API returns plain data")
+}
+
+#[wasm_bindgen_test]
+fn parse_and_describe() {
+    let game = Game::new(EXAMPLE).expect("Valid board");
+    assert_eq!(game.notation(), EXAMPLE);
+    let position = json(game.position().unwrap());
+    assert_eq!(position["turn"], 0);
+    assert_eq!(position["ply"], 0);
+    assert_eq!(position["pawns"][0], json!({"row": 1, "col": 3}));
+    assert_eq!(position["cards"][0], json!([1, 2, 2, 3]));
+    assert_eq!(position["score"], Value::Null);
+    assert!(Game::new("1223/4121/3123/1b314/0").is_err());
+}
+
+#[wasm_bindgen_test]
+fn make_and_undo_moves() {
+    let mut game = Game::new(EXAMPLE).unwrap();
+    let moves = json(game.legal_moves().unwrap());
+    assert_eq!(moves.as_array().unwrap().len(), 4);
+    assert!(game.make_move(1, 3).is_err());
+    game.make_move(1, 0).unwrap();
+    assert_eq!(game.notation(), "1223/4r120/3123/1b314/1");
+    game.undo_move().unwrap();
+    assert_eq!(game.notation(), EXAMPLE);
+    assert!(game.undo_move().is_err());
+}
+
+#[wasm_bindgen_test]
+fn move_paths() {
+    let game = Game::new(EXAMPLE).unwrap();
+    let moves = json(game.move_paths().unwrap());
+    assert_eq!(moves.as_array().unwrap().len(), 4);
+    // Red's first move from its jack wraps around from column 3 to column 0
+    assert_eq!(moves[1]["row"], 1);
+    assert_eq!(moves[1]["col"], 0);
+    assert_eq!(
+        moves[1]["paths"],
+        json!([{
+            "squares": [{"row": 1, "col": 3}, {"row": 1, "col": 0}],
+            "wraps": [true],
+        }])
+    );
+}
+
+#[wasm_bindgen_test]
+fn solve_and_evaluate() {
+    let mut game = Game::new(EXAMPLE).unwrap();
+    let solution = json(game.solve().unwrap());
+    assert_eq!(solution["score"], 3);
+    let evaluations = json(game.evaluate_moves().unwrap());
+    let best = evaluations
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|e| {
+            e["row"] == solution["best_move"]["row"] && e["col"] == solution["best_move"]["col"]
+        })
+        .unwrap();
+    assert_eq!(best["score"], 3);
+    assert!(
+        evaluations
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|e| e["score"].as_i64() <= Some(3))
+    );
+}
+
+#[wasm_bindgen_test]
+fn random_deals_are_reproducible() {
+    let game = Game::random(7, false);
+    assert_eq!(game.notation(), Game::random(7, false).notation());
+    assert!(game.notation().ends_with("/0"));
+}
@@ -0,0 +1,187 @@
+// Play Collapsi in the browser, using the solver compiled to WebAssembly.
+// Build the pkg/ directory first (see build.sh).
+
+import init, { Game } from "./pkg/collapsi.js";
+
+const SIZE = 4;
+const CELL = 86; // card width plus the gap between cards, in pixels
+const STEP_MS = 200;
+const NAMES = ["Red", "Blue"];
+
+let game;
+let position;
+let busy = false;
+
+const boardElement = document.getElementById("board");
+const statusElement = document.getElementById("status");
+const modeElement = document.getElementById("mode");
+const oldElement = document.getElementById("old");
+const notationElement = document.getElementById("notation");
+
+/// Whether the solver plays the side to move
+function solverToMove() {
+  const mode = modeElement.value;
+  return (mode === "red" && position.turn === 1) || (mode === "blue" && position.turn === 0);
+}
+
+function pawnTransform(point) {
+  return `translate(${point.col * CELL}px, ${point.row * CELL}px)`;
+}
+
+function sleep(ms) {
+  return new Promise((resolve) => setTimeout(resolve, ms));
+}
+
+/// Move the pawn of the player to move along a path from `Game.move_paths`,
+/// one card at a time
+async function animate(path) {
+  const pawn = boardElement.querySelector(`.pawn.${NAMES[position.turn].toLowerCase()}`);
+  for (let i = 1; i < path.squares.length; i++) {
+    const b = path.squares[i];
+    if (path.wraps[i - 1]) {
+      // Leave by one edge and come back on the opposite one
+      pawn.style.opacity = 0;
+      await sleep(STEP_MS / 2);
+      pawn.style.transition = "none";
+      pawn.style.transform = pawnTransform(b);
+      pawn.getBoundingClientRect();
+      pawn.style.transition = "";
+      pawn.style.opacity = 1;
+      await sleep(STEP_MS / 2);
+    } else {
+      pawn.style.transform = pawnTransform(b);
+      await sleep(STEP_MS);
+    }
+  }
+}
+
+async function play(move) {
+  const paths = game.move_paths().find((m) => m.row === move.row && m.col === move.col).paths;
+  busy = true;
+  await animate(paths[0]);
+  game.make_move(move.row, move.col);
+  busy = false;
+  update();
+}
+
+function render(marks = {}) {
+  boardElement.replaceChildren();
+  for (let row = 0; row < SIZE; row++) {
+    for (let col = 0; col < SIZE; col++) {
+      const card = document.createElement("div");
+      const value = position.cards[row][col];
+      const occupied = position.pawns.some((p) => p.row === row && p.col === col);
+      card.className = "card";
+      card.textContent = value === 0 && occupied ? "J" : value;
+      if (value === 0 && !occupied) {
+        card.classList.add("flipped");
+      }
+      const move = position.legal_moves.find((m) => m.row === row && m.col === col);
+      if (move && !busy && !solverToMove()) {
+        card.classList.add("legal");
+        card.addEventListener("click", () => play(move));
+      }
+      if (marks.hint && marks.hint.row === row && marks.hint.col === col) {
+        card.classList.add("hint");
+      }
+      const evaluation = marks.evaluations?.find((e) => e.row === row && e.col === col);
+      if (evaluation) {
+        const label = document.createElement("span");
+        label.className = "evaluation";
+        label.textContent = describeScore(evaluation.score);
+        card.append(label);
+      }
+      boardElement.append(card);
+    }
+  }
+  position.pawns.forEach((point, player) => {
+    const pawn = document.createElement("div");
+    pawn.className = `pawn ${NAMES[player].toLowerCase()}`;
+    pawn.style.transform = pawnTransform(point);
+    boardElement.append(pawn);
+  });
+}
+
+/// A score from red's point of view, in words
+function describeScore(score) {
+  return `${NAMES[score > 0 ? 0 : 1]} wins by ${Math.abs(score)}`;
+}
+
+function update() {
+  position = game.position();
+  notationElement.value = position.notation;
+  render();
+  if (position.score !== undefined && position.score !== null) {
+    statusElement.textContent = `${NAMES[position.turn]} cannot move. ${describeScore(position.score)}.`;
+  } else if (solverToMove()) {
+    statusElement.textContent = `${NAMES[position.turn]} (solver) is thinking…`;
+    busy = true;
+    // Let the page redraw before the search blocks it
+    setTimeout(() => {
+      const solution = game.solve();
+      play(solution.best_move);
+    }, 50);
+  } else {
+    statusElement.textContent = `${NAMES[position.turn]} to play`;
+  }
+}
+
+function newDeal() {
+  game = Game.random(Math.floor(Math.random() * 2 ** 32), oldElement.checked);
+  update();
+}
+
+document.getElementById("hint").addEventListener("click", () => {
+  if (busy || position.legal_moves.length === 0) {
+    return;
+  }
+  const solution = game.solve();
+  render({ hint: solution.best_move });
+  statusElement.textContent = `Hint: ${describeScore(solution.score)}`;
+});
+
+document.getElementById("evaluate").addEventListener("click", () => {
+  if (!busy) {
+    render({ evaluations: game.evaluate_moves() });
+  }
+});
+
+document.getElementById("undo").addEventListener("click", () => {
+  if (busy) {
+    return;
+  }
+  // Against the solver, take back the solver's reply as well
+  const plies = modeElement.value === "humans" ? 1 : 2;
+  for (let i = 0; i < plies; i++) {
+    try {
+      game.undo_move();
+    } catch {
+      break;
+    }
+  }
+  update();
+});
+
+document.getElementById("new").addEventListener("click", () => {
+  if (!busy) {
+    newDeal();
+  }
+});
+modeElement.addEventListener("change", () => {
+  if (!busy) {
+    update();
+  }
+});
+
+document.getElementById("load").addEventListener("submit", (event) => {
+  event.preventDefault();
+  try {
+    game = new Game(notationElement.value.trim());
+    update();
+  } catch (error) {
+    statusElement.textContent = `Invalid board: ${error.message}`;
+  }
+});
+
+await init();
+newDeal();
@@ -0,0 +1,12 @@
+#!/bin/sh
+# Compile the solver to WebAssembly and generate its JavaScript bindings in
+# web/pkg, ready to serve the web/ directory as static files.
+#
+# Needs the wasm32-unknown-unknown target (rustup target add
+# wasm32-unknown-unknown) and wasm-bindgen-cli at the same version as the
+# wasm-bindgen dependency.
+set -e
+cd "$(dirname "$0")/.."
+cargo build --release --lib --target wasm32-unknown-unknown --no-default-features --features wasm
+wasm-bindgen --target web --no-typescript --out-dir web/pkg \
+  target/wasm32-unknown-unknown/release/collapsi.wasm